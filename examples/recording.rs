@@ -1,8 +1,8 @@
 use asterisk_ari_client_rs::apis::channels::ChannelsAPI;
 use asterisk_ari_client_rs::apis::recordings::RecordingsAPI;
 use asterisk_ari_client_rs::models::events::*;
+use asterisk_ari_client_rs::models::recordings::RecordOptions;
 use asterisk_ari_client_rs::{client::AriClient, errors::Result};
-use env_logger;
 use lazy_static::lazy_static;
 use log::*;
 use std::fs;
@@ -28,7 +28,7 @@ fn stasis_start(event: StasisStart) {
 
         // start the recording
         ARICLIENT
-            .record(&event.channel.id, None, None, None, None, None, None, None)
+            .record(&event.channel.id, RecordOptions::default())
             .await
             .unwrap();
 
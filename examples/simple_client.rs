@@ -1,8 +1,8 @@
 use asterisk_ari_client_rs::apis::applications::ApplicationsAPI;
 use asterisk_ari_client_rs::apis::channels::ChannelsAPI;
 use asterisk_ari_client_rs::models::events::*;
+use asterisk_ari_client_rs::models::recordings::RecordOptions;
 use asterisk_ari_client_rs::{client::AriClient, errors::Result};
-use env_logger;
 use lazy_static::lazy_static;
 use log::*;
 use std::time::Duration;
@@ -24,7 +24,7 @@ fn stasis_start(event: StasisStart) {
         ARICLIENT.answer(&event.channel.id).await.unwrap();
         // do the recording
         ARICLIENT
-            .record(&event.channel.id, None, None, None, None, None, None, None)
+            .record(&event.channel.id, RecordOptions::default())
             .await
             .unwrap();
         debug!("Channel {} answered!", &event.channel.id);
@@ -0,0 +1,50 @@
+use asterisk_ari_client_rs::app::StasisApp;
+use asterisk_ari_client_rs::dtmf::DtmfOptions;
+use asterisk_ari_client_rs::models::events::AriEvent;
+use asterisk_ari_client_rs::{client::AriClient, errors::Result};
+use log::*;
+use std::time::Duration;
+use tokio::time::sleep;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+    let client = AriClient::new(
+        "http://localhost:8088/ari".into(),
+        "asterisk".into(),
+        "asterisk".into(),
+    );
+
+    let mut app = StasisApp::new(client);
+    app.on_call(|mut call| async move {
+        info!("new call {}", call.channel_id());
+        call.answer().await?;
+
+        let digits = call
+            .collect_dtmf(DtmfOptions {
+                max_digits: Some(4),
+                prompt: Some("sound:hello-world".into()),
+                ..Default::default()
+            })
+            .await?;
+        info!("call {} entered {:?}", call.channel_id(), digits);
+
+        let playback = call.play("sound:demo-congrats").await?;
+        while let Some(event) = call.next_event().await {
+            match event {
+                AriEvent::PlaybackFinished(event) if event.playback.id == playback.id => break,
+                _ => {}
+            }
+        }
+        call.hangup().await
+    });
+
+    // stop accepting calls after 100 seconds, run() returns once active calls are finished
+    let shutdown = app.shutdown_handle();
+    tokio::spawn(async move {
+        sleep(Duration::from_millis(100000)).await;
+        shutdown.shutdown();
+    });
+
+    app.run(vec!["my-ast-app".into()]).await
+}
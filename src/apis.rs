@@ -1,3 +1,22 @@
+use crate::models::events::AriEvent;
+use tokio::sync::broadcast;
+
 pub mod applications;
+pub mod bridges;
 pub mod channels;
 pub mod recordings;
+
+/// All ARI operations combined, implemented by [AriClient](crate::client::AriClient).
+/// Object safe, i.e. application code can depend on `Arc<dyn Ari>` and unit tests can inject
+/// [MockAri](crate::testing::MockAri) (feature `testing`) instead of the real client.
+pub trait Ari:
+    applications::ApplicationsAPI
+    + bridges::BridgesAPI
+    + channels::ChannelsAPI
+    + recordings::RecordingsAPI
+    + Send
+    + Sync
+{
+    /// Subscribe to all received ARI events, see [subscribe_events](crate::client::AriClient::subscribe_events).
+    fn subscribe_events(&self) -> broadcast::Receiver<AriEvent>;
+}
@@ -0,0 +1,12 @@
+use crate::errors::Result;
+use crate::models::bridges::Bridge;
+use async_trait::async_trait;
+
+#[async_trait]
+pub trait BridgesAPI {
+    /// List all active bridges in Asterisk.
+    async fn list_bridges(&self) -> Result<Vec<Bridge>>;
+
+    /// Get bridge details.
+    async fn get_bridge(&self, bridge_id: &str) -> Result<Bridge>;
+}
@@ -1,10 +1,27 @@
 use crate::errors::Result;
-use crate::models::channels::{Channel, Direction};
+use crate::models::channels::{Channel, CreateChannelOptions, OriginateOptions, SnoopOptions};
 use crate::models::playbacks::Playback;
+use crate::models::recordings::{RecordOptions, Recording};
 use async_trait::async_trait;
+use futures_util::future::try_join_all;
+use std::collections::HashMap;
 
 #[async_trait]
 pub trait ChannelsAPI {
+    /// List all active channels in Asterisk.
+    async fn list_channels(&self) -> Result<Vec<Channel>>;
+
+    /// Create a new channel (originate) and connect it to dialplan location or Stasis application once answered.
+    async fn originate(&self, endpoint: &str, options: OriginateOptions) -> Result<Channel>;
+
+    /// Create a new channel in Stasis application without dialing it (see dial).
+    async fn create_channel(
+        &self,
+        endpoint: &str,
+        app: &str,
+        options: CreateChannelOptions,
+    ) -> Result<Channel>;
+
     /// Answer a channel.
     async fn answer(&self, channel_id: &str) -> Result<()>;
     /// Play media to a channel
@@ -12,10 +29,10 @@ pub trait ChannelsAPI {
         &self,
         channel_id: &str,
         media: &str,
-        _playback_id: Option<String>,
-        _lang: Option<String>,
-        _offsetms: Option<usize>,
-        _skipms: Option<usize>,
+        playback_id: Option<String>,
+        lang: Option<String>,
+        offsetms: Option<usize>,
+        skipms: Option<usize>,
     ) -> Result<Playback>;
     /// Stop playing particular playback
     async fn stop_play(&self, playback_id: &str) -> Result<()>;
@@ -23,6 +40,28 @@ pub trait ChannelsAPI {
     /// Get the value of a channel variable
     async fn get_variable(&self, channel_id: &str, var_name: &str) -> Result<String>;
 
+    /// Get the values of several channel variables, variables are fetched concurrently.
+    async fn get_variables(
+        &self,
+        channel_id: &str,
+        var_names: &[&str],
+    ) -> Result<HashMap<String, String>>
+    where
+        Self: Sync,
+    {
+        let values = try_join_all(
+            var_names
+                .iter()
+                .map(|var_name| self.get_variable(channel_id, var_name)),
+        )
+        .await?;
+        Ok(var_names
+            .iter()
+            .map(|var_name| var_name.to_string())
+            .zip(values)
+            .collect())
+    }
+
     /// Set the value of a channel variable
     async fn set_variable(&self, channel_id: &str, var_name: &str, var_value: &str) -> Result<()>;
 
@@ -33,25 +72,8 @@ pub trait ChannelsAPI {
     async fn continue_in_dialplan(&self, channel_id: &str) -> Result<()>;
 
     /// Create a new channel to snoop (spy/whisper) on a specific channel
-    async fn snoop(
-        &self,
-        channel_id: &str,
-        app: &str,
-        spy: Option<Direction>,
-        whisper: Option<Direction>,
-    ) -> Result<Channel>;
+    async fn snoop(&self, channel_id: &str, app: &str, options: SnoopOptions) -> Result<Channel>;
 
-    /// Record audio from a channel. Default filepath: /var/spool/asterisk/recording/channel_id.wav
-    #[allow(clippy::too_many_arguments)]
-    async fn record(
-        &self,
-        channel_id: &str,
-        filepath: Option<&str>,
-        audio_format: Option<&str>,
-        terminate_on: Option<&str>,
-        max_duration: Option<usize>,
-        max_silence: Option<usize>,
-        if_exists: Option<&str>,
-        beep: Option<bool>,
-    ) -> Result<()>;
+    /// Start recording audio from a channel, returns the live recording.
+    async fn record(&self, channel_id: &str, options: RecordOptions) -> Result<Recording>;
 }
@@ -0,0 +1,465 @@
+//! High level Stasis application framework. Instead of handling raw events
+//! application registers single async handler invoked (in separate task) for every call entering Stasis:
+//!
+//! ```no_run
+//! use asterisk_ari_client_rs::{app::StasisApp, client::AriClient};
+//!
+//! # async fn example() -> asterisk_ari_client_rs::errors::Result<()> {
+//! let client = AriClient::new("http://localhost:8088/ari".into(), "asterisk".into(), "asterisk".into());
+//! let mut app = StasisApp::new(client);
+//! app.on_call(|call| async move {
+//!     call.answer().await?;
+//!     call.play("sound:hello-world").await?;
+//!     call.hangup().await
+//! });
+//! app.run(vec!["my-ast-app".into()]).await
+//! # }
+//! ```
+
+use crate::apis::channels::ChannelsAPI;
+use crate::client::AriClient;
+use crate::dtmf::{collect_dtmf_from, DtmfOptions, DtmfResult};
+use crate::errors::Result;
+use crate::models::channels::{Channel, SnoopOptions};
+use crate::models::events::AriEvent;
+use crate::models::playbacks::Playback;
+use crate::models::recordings::{RecordOptions, Recording};
+use crate::sink::BackpressurePolicy;
+use futures_util::future::BoxFuture;
+use log::*;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinSet;
+
+type CallHandler = dyn Fn(Call) -> BoxFuture<'static, Result<()>> + Send + Sync;
+
+/// Single call (channel) handled by the Stasis application.
+/// Carries channel id and stream of events related to this channel only.
+pub struct Call {
+    client: AriClient,
+    channel: Channel,
+    args: Vec<String>,
+    events: mpsc::UnboundedReceiver<AriEvent>,
+}
+
+impl Call {
+    /// Id of the channel this call is bound to.
+    pub fn channel_id(&self) -> &str {
+        &self.channel.id
+    }
+
+    /// Channel as reported in StasisStart event.
+    pub fn channel(&self) -> &Channel {
+        &self.channel
+    }
+
+    /// Arguments passed to Stasis application in dialplan.
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Client used by this call, e.g. to invoke operations not covered by Call itself.
+    pub fn client(&self) -> &AriClient {
+        &self.client
+    }
+
+    /// Next event related to this call (including playbacks and recordings targeting the channel).
+    /// Returns None once the channel has left Stasis application (StasisEnd is the last event delivered).
+    pub async fn next_event(&mut self) -> Option<AriEvent> {
+        self.events.recv().await
+    }
+
+    /// Answer the channel.
+    pub async fn answer(&self) -> Result<()> {
+        self.client.answer(&self.channel.id).await
+    }
+
+    /// Play media to the channel.
+    pub async fn play(&self, media: &str) -> Result<Playback> {
+        self.client
+            .play(&self.channel.id, media, None, None, None, None)
+            .await
+    }
+
+    /// Stop playing particular playback.
+    pub async fn stop_play(&self, playback_id: &str) -> Result<()> {
+        self.client.stop_play(playback_id).await
+    }
+
+    /// Get the value of a channel variable.
+    pub async fn get_variable(&self, var_name: &str) -> Result<String> {
+        self.client.get_variable(&self.channel.id, var_name).await
+    }
+
+    /// Get the values of several channel variables (fetched concurrently).
+    pub async fn get_variables(&self, var_names: &[&str]) -> Result<HashMap<String, String>> {
+        self.client.get_variables(&self.channel.id, var_names).await
+    }
+
+    /// Set the value of a channel variable.
+    pub async fn set_variable(&self, var_name: &str, var_value: &str) -> Result<()> {
+        self.client
+            .set_variable(&self.channel.id, var_name, var_value)
+            .await
+    }
+
+    /// Hang up the channel.
+    pub async fn hangup(&self) -> Result<()> {
+        self.client.hangup(&self.channel.id).await
+    }
+
+    /// Exit application; continue execution in the dialplan.
+    pub async fn continue_in_dialplan(&self) -> Result<()> {
+        self.client.continue_in_dialplan(&self.channel.id).await
+    }
+
+    /// Create a new channel to snoop (spy/whisper) on this channel.
+    pub async fn snoop(&self, app: &str, options: SnoopOptions) -> Result<Channel> {
+        self.client.snoop(&self.channel.id, app, options).await
+    }
+
+    /// Record audio from the channel, recording name defaults to the channel id.
+    pub async fn record(&self, options: RecordOptions) -> Result<Recording> {
+        self.client.record(&self.channel.id, options).await
+    }
+
+    /// Gather DTMF digits from the channel. Events are taken from call's own stream,
+    /// i.e. events received during the collection (e.g. prompt playback) are not returned by [next_event](Self::next_event).
+    pub async fn collect_dtmf(&mut self, options: DtmfOptions) -> Result<DtmfResult> {
+        collect_dtmf_from(&self.client, &mut self.events, &self.channel.id, options).await
+    }
+}
+
+/// Handle used to gracefully stop running [StasisApp].
+#[derive(Clone)]
+pub struct AppShutdownHandle {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl AppShutdownHandle {
+    /// Stop accepting new calls, they continue in the dialplan. Once all active calls are finished websocket connection
+    /// is closed (see [ShutdownHandle](crate::connection::ShutdownHandle)) and [StasisApp::run] returns.
+    pub fn shutdown(&self) {
+        let _ = self.sender.send(true);
+    }
+}
+
+/// Stasis application spawning a task with [Call] for every StasisStart event.
+pub struct StasisApp {
+    client: AriClient,
+    handler: Option<Arc<CallHandler>>,
+    shutdown_sender: Arc<watch::Sender<bool>>,
+}
+
+impl StasisApp {
+    pub fn new(client: AriClient) -> Self {
+        StasisApp {
+            client,
+            handler: None,
+            shutdown_sender: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    /// Register handler invoked for every new call.
+    pub fn on_call<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(Call) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.handler = Some(Arc::new(move |call| Box::pin(handler(call))));
+        self
+    }
+
+    pub fn shutdown_handle(&self) -> AppShutdownHandle {
+        AppShutdownHandle {
+            sender: self.shutdown_sender.clone(),
+        }
+    }
+
+    /// Connect to ARI and dispatch calls to registered handler until shutdown is requested
+    /// (and all active calls are finished) or the websocket connection ends.
+    pub async fn run(&self, asterisk_apps: Vec<String>) -> Result<()> {
+        // own unbounded queue, lost StasisStart/StasisEnd would leave calls unhandled or never finished
+        let mut events = self
+            .client
+            .subscribe_events_with_policy(1024, BackpressurePolicy::default());
+        let mut shutdown_receiver = self.shutdown_sender.subscribe();
+        let mut shutting_down = *shutdown_receiver.borrow();
+
+        let processing_loop = self.client.ari_processing_loop(asterisk_apps);
+        tokio::pin!(processing_loop);
+
+        let mut calls: HashMap<String, mpsc::UnboundedSender<AriEvent>> = HashMap::new();
+        let mut tasks = JoinSet::new();
+        let mut result = Ok(());
+
+        loop {
+            if shutting_down && tasks.is_empty() {
+                info!("StasisApp: all calls finished, shutting down");
+                // close the websocket cleanly and let the loop deliver remaining events
+                self.client.shutdown_handle().shutdown();
+                result = (&mut processing_loop).await;
+                break;
+            }
+
+            tokio::select! {
+                loop_result = &mut processing_loop => {
+                    result = loop_result;
+                    break;
+                }
+                event = events.recv() => {
+                    match event {
+                        Some(event) => self.route_event(event, &mut calls, &mut tasks, shutting_down),
+                        None => break,
+                    }
+                }
+                changed = shutdown_receiver.changed(), if !shutting_down => {
+                    // sender is owned by self, i.e. error cannot really happen
+                    shutting_down = changed.is_err() || *shutdown_receiver.borrow();
+                    if shutting_down {
+                        info!("StasisApp: shutdown requested, waiting for {} active calls", tasks.len());
+                    }
+                }
+                Some(joined) = tasks.join_next(), if !tasks.is_empty() => {
+                    if let Err(join_err) = joined {
+                        error!("StasisApp: call handler panicked {:?}", join_err);
+                    }
+                }
+            }
+        }
+
+        // websocket is gone, end event streams of remaining calls and let handlers finish
+        calls.clear();
+        while let Some(joined) = tasks.join_next().await {
+            if let Err(join_err) = joined {
+                error!("StasisApp: call handler panicked {:?}", join_err);
+            }
+        }
+
+        result
+    }
+
+    fn route_event(
+        &self,
+        event: AriEvent,
+        calls: &mut HashMap<String, mpsc::UnboundedSender<AriEvent>>,
+        tasks: &mut JoinSet<()>,
+        shutting_down: bool,
+    ) {
+        let channel_id = match event.channel_id() {
+            Some(channel_id) => channel_id.to_owned(),
+            None => return,
+        };
+
+        match event {
+            AriEvent::StasisStart(event) => {
+                if shutting_down {
+                    warn!("StasisApp: shutting down, call {} not accepted", channel_id);
+                    // do not leave the caller stuck in Stasis, shutdown waits for the task as for any call
+                    let client = self.client.clone();
+                    tasks.spawn(async move {
+                        if let Err(err) = client.continue_in_dialplan(&channel_id).await {
+                            error!("StasisApp: call {} continue failed {:?}", channel_id, err);
+                        }
+                    });
+                    return;
+                }
+                let handler = match &self.handler {
+                    Some(handler) => handler.clone(),
+                    None => {
+                        warn!("StasisApp: no call handler registered");
+                        return;
+                    }
+                };
+
+                let (sender, receiver) = mpsc::unbounded_channel();
+                calls.insert(channel_id.clone(), sender);
+                let call = Call {
+                    client: self.client.clone(),
+                    channel: event.channel,
+                    args: event.args,
+                    events: receiver,
+                };
+                tasks.spawn(async move {
+                    if let Err(err) = handler(call).await {
+                        error!("StasisApp: call {} handler failed {:?}", channel_id, err);
+                    }
+                });
+            }
+            AriEvent::StasisEnd(_) => {
+                // last event of the call, dropping the sender ends call's event stream
+                if let Some(sender) = calls.remove(&channel_id) {
+                    let _ = sender.send(event);
+                }
+            }
+            _ => {
+                if let Some(sender) = calls.get(&channel_id) {
+                    let _ = sender.send(event);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixtures, AsteriskSimulator};
+    use reqwest::Method;
+    use tokio::time::{timeout, Duration};
+
+    const STR_JSON_PLAYBACK_STARTED: &str = "{\n  \"type\": \"PlaybackStarted\",\n  \"timestamp\": \"2020-12-08T19:10:36.254+0000\",\n  \"playback\": {\n    \"id\": \"7b4ed6c5-5d0f-4f5d-9c6a-2c4e3a3b7f52\",\n    \"media_uri\": \"sound:hello-world\",\n    \"target_uri\": \"channel:1607454635.2\",\n    \"language\": \"en\",\n    \"state\": \"playing\"\n  },\n  \"asterisk_id\": \"00:15:5d:01:65:04\",\n  \"application\": \"my-ast-app\"\n}";
+
+    // cargo test -- --show-output test_event_channel_id_from_target_uri
+    #[test]
+    fn test_event_channel_id_from_target_uri() {
+        let ari_event: AriEvent = serde_json::from_str(STR_JSON_PLAYBACK_STARTED).unwrap();
+        assert_eq!(ari_event.channel_id(), Some("1607454635.2"));
+    }
+
+    // cargo test -- --show-output test_stasis_app_event_burst
+    #[tokio::test]
+    async fn test_stasis_app_event_burst() {
+        let simulator = AsteriskSimulator::start().await;
+        let client = simulator.client();
+        let mut app = StasisApp::new(client.clone());
+        let (sender, mut calls) = mpsc::unbounded_channel();
+        app.on_call(move |mut call| {
+            let sender = sender.clone();
+            async move {
+                let _ = sender.send(None);
+                let mut digits = 0;
+                while let Some(event) = call.next_event().await {
+                    if let AriEvent::ChannelDtmfReceived(_) = event {
+                        digits += 1;
+                    }
+                }
+                let _ = sender.send(Some(digits));
+                Ok(())
+            }
+        });
+        let shutdown = app.shutdown_handle();
+        let app = tokio::spawn(async move { app.run(vec!["ivr".into()]).await });
+        simulator.wait_for_websocket().await;
+
+        let channel = simulator.incoming_call("ivr", "1004", &[]);
+        assert_eq!(calls.recv().await, Some(None));
+        // burst larger than capacity of the event bus, no event may be lost
+        let dtmf = fixtures::dtmf(&channel.id, "1");
+        for _ in 0..2000 {
+            client.dispatch_event(dtmf.clone()).await;
+        }
+        simulator.caller_hangup(&channel.id);
+
+        let digits = timeout(Duration::from_secs(10), calls.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(digits, Some(2000));
+
+        shutdown.shutdown();
+        timeout(Duration::from_secs(5), app)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    // cargo test -- --show-output test_stasis_app_collect_dtmf_then_play
+    #[tokio::test(start_paused = true)]
+    async fn test_stasis_app_collect_dtmf_then_play() {
+        let simulator = AsteriskSimulator::start().await;
+        simulator.set_media_duration("sound:enter-pin", Duration::from_secs(3));
+        let client = simulator.client();
+        let mut events = client.subscribe_events();
+        let mut app = StasisApp::new(client.clone());
+        let (sender, mut results) = mpsc::unbounded_channel();
+        app.on_call(move |mut call| {
+            let sender = sender.clone();
+            async move {
+                let digits = call
+                    .collect_dtmf(DtmfOptions {
+                        max_digits: Some(2),
+                        prompt: Some("sound:enter-pin".into()),
+                        ..Default::default()
+                    })
+                    .await?;
+                let _ = sender.send(format!("{:?}", digits));
+                // events of the prompt must not show up in call's stream
+                call.play("sound:goodbye").await?;
+                while let Some(event) = call.next_event().await {
+                    if let AriEvent::PlaybackFinished(event) = event {
+                        let _ = sender.send(event.playback.media_uri);
+                        break;
+                    }
+                }
+                call.hangup().await
+            }
+        });
+        let shutdown = app.shutdown_handle();
+        let app = tokio::spawn(async move { app.run(vec!["ivr".into()]).await });
+        simulator.wait_for_websocket().await;
+
+        let channel = simulator.incoming_call("ivr", "1004", &[]);
+        loop {
+            if let Ok(AriEvent::PlaybackFinished(_)) = events.recv().await {
+                break;
+            }
+        }
+        simulator.send_dtmf(&channel.id, "42");
+        assert_eq!(
+            results.recv().await.unwrap(),
+            format!("{:?}", DtmfResult::Complete("42".into()))
+        );
+        assert_eq!(results.recv().await.unwrap(), "sound:goodbye");
+
+        shutdown.shutdown();
+        app.await.unwrap().unwrap();
+    }
+
+    // cargo test -- --show-output test_stasis_app_shutdown_continues_new_call
+    #[tokio::test]
+    async fn test_stasis_app_shutdown_continues_new_call() {
+        let simulator = AsteriskSimulator::start().await;
+        let mut app = StasisApp::new(simulator.client());
+        let (sender, mut calls) = mpsc::unbounded_channel();
+        app.on_call(move |mut call| {
+            let sender = sender.clone();
+            async move {
+                let _ = sender.send(call.channel_id().to_owned());
+                while call.next_event().await.is_some() {}
+                Ok(())
+            }
+        });
+        let shutdown = app.shutdown_handle();
+        let app = tokio::spawn(async move { app.run(vec!["ivr".into()]).await });
+        simulator.wait_for_websocket().await;
+
+        // active call keeps the application running
+        let active = simulator.incoming_call("ivr", "1004", &[]);
+        assert_eq!(calls.recv().await.as_deref(), Some(active.id.as_str()));
+        shutdown.shutdown();
+
+        let rejected = simulator.incoming_call("ivr", "1005", &[]);
+        let path = format!("/channels/{}/continue", rejected.id);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while simulator
+            .server()
+            .requests_to(&Method::POST, &path)
+            .is_empty()
+        {
+            assert!(std::time::Instant::now() < deadline, "call not continued");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        simulator.caller_hangup(&active.id);
+        timeout(Duration::from_secs(5), app)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(calls.try_recv().is_err());
+    }
+}
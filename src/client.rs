@@ -1,32 +1,75 @@
 use crate::apis::{
-    applications::ApplicationsAPI, channels::ChannelsAPI, recordings::RecordingsAPI,
+    applications::ApplicationsAPI, bridges::BridgesAPI, channels::ChannelsAPI,
+    recordings::RecordingsAPI, Ari,
+};
+use crate::connection::{
+    ConnectionOptions, ConnectionState, KeepaliveConfig, ReconnectPolicy, ShutdownHandle,
+    WebsocketAuth,
 };
 use crate::errors::{Error, Result};
 use crate::models::applications::Application;
-use crate::models::channels::{Channel, Direction, Variable};
+use crate::models::bridges::Bridge;
+use crate::models::channels::{
+    Channel, CreateChannelOptions, OriginateOptions, SnoopOptions, Variable,
+};
 use crate::models::events::*;
 use crate::models::playbacks::Playback;
+use crate::models::recordings::{RecordOptions, Recording};
+use crate::replay::EventRecorder;
+use crate::retry::RetryPolicy;
+use crate::sink::{
+    event_queue, BackpressurePolicy, DeliveryStats, EventQueueReceiver, EventSink, TypedSender,
+};
+use crate::state::StateCache;
 use async_trait::async_trait;
+use futures_util::future::BoxFuture;
+use futures_util::stream::SplitSink;
 use futures_util::SinkExt;
-use lazy_static::lazy_static;
 use log::*;
-use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use native_tls::TlsConnector;
 use rand::Rng;
-use reqwest::StatusCode;
 use reqwest::{
     self,
     header::{HeaderMap, HeaderValue},
+    Method, Response, StatusCode,
+};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, mpsc::Sender, watch};
+use tokio::time::{interval, sleep, sleep_until, Duration, Instant, Interval};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue as WSHeaderValue;
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
+use tokio_tungstenite::tungstenite::Error as WSError;
+use tokio_tungstenite::{
+    connect_async_tls_with_config, tungstenite::Message as WSMessage, Connector, MaybeTlsStream,
+    WebSocketStream,
 };
-use tokio::sync::mpsc::Sender;
-use tokio::time::{interval, Duration};
-use tokio_tungstenite::{connect_async, tungstenite::Message as WSMessage};
 use url::Url;
-use urlencoding::encode;
 
-lazy_static! {
-    pub static ref HTTP_CLIENT: reqwest::Client = reqwest::Client::new();
+mod builder;
+mod requests;
+pub use builder::AriClientBuilder;
+use requests::*;
+
+/// How long to wait for Asterisk to acknowledge websocket Close frame sent during shutdown.
+const CLOSE_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// Capacity of the broadcast channel carrying every received ARI event.
+/// Subscribers lagging behind by more than this number of events will miss the oldest ones.
+const EVENT_BUS_CAPACITY: usize = 1024;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// reason why websocket processing ended without error
+enum WsExit {
+    Closed,
+    OptionsChanged,
 }
 
+type ReconnectHook = dyn Fn(AriClient) -> BoxFuture<'static, ()> + Send + Sync;
+
 // items from traits can only be used if the trait is in scope
 // this is brought in scope so that ws_stream.next() works!
 use futures_util::StreamExt; // SinkExt needed for ws_stream.send(msg);
@@ -36,23 +79,53 @@ pub struct AriClient {
     pub url: String,
     pub user: String,
     pub password: String,
-    stasis_start_sender: Option<Sender<StasisStart>>,
-    channel_dtmf_received_sender: Option<Sender<ChannelDtmfReceived>>,
-    channel_hangup_request_sender: Option<Sender<ChannelHangupRequest>>,
-    stasis_end_sender: Option<Sender<StasisEnd>>,
-    channel_talking_finished_sender: Option<Sender<ChannelTalkingFinished>>,
-    channel_talking_started_sender: Option<Sender<ChannelTalkingStarted>>,
-    channel_destroyed_sender: Option<Sender<ChannelDestroyed>>,
-    playback_started_sender: Option<Sender<PlaybackStarted>>,
-    playback_finished_sender: Option<Sender<PlaybackFinished>>,
-    channel_state_change_sender: Option<Sender<ChannelStateChange>>,
-    channel_var_set_sender: Option<Sender<ChannelVarset>>,
-    recording_started_sender: Option<Sender<RecordingStarted>>,
-    recording_finished_sender: Option<Sender<RecordingFinished>>,
+    stasis_start_sender: Option<TypedSender<StasisStart>>,
+    channel_dtmf_received_sender: Option<TypedSender<ChannelDtmfReceived>>,
+    channel_hangup_request_sender: Option<TypedSender<ChannelHangupRequest>>,
+    stasis_end_sender: Option<TypedSender<StasisEnd>>,
+    channel_talking_finished_sender: Option<TypedSender<ChannelTalkingFinished>>,
+    channel_talking_started_sender: Option<TypedSender<ChannelTalkingStarted>>,
+    channel_destroyed_sender: Option<TypedSender<ChannelDestroyed>>,
+    playback_started_sender: Option<TypedSender<PlaybackStarted>>,
+    playback_finished_sender: Option<TypedSender<PlaybackFinished>>,
+    channel_state_change_sender: Option<TypedSender<ChannelStateChange>>,
+    channel_var_set_sender: Option<TypedSender<ChannelVarset>>,
+    recording_started_sender: Option<TypedSender<RecordingStarted>>,
+    recording_finished_sender: Option<TypedSender<RecordingFinished>>,
+    event_bus: broadcast::Sender<AriEvent>,
+    sender_policy: BackpressurePolicy,
+    event_sinks: Arc<std::sync::Mutex<Vec<Arc<dyn EventSink>>>>,
+    reconnect_policy: Option<ReconnectPolicy>,
+    connection_state_sender: Option<Sender<ConnectionState>>,
+    reconnect_hook: Option<Arc<ReconnectHook>>,
+    shutdown: ShutdownHandle,
+    keepalive: KeepaliveConfig,
+    connection_options: Arc<watch::Sender<ConnectionOptions>>,
+    http_client: reqwest::Client,
+    tls_connector: Option<TlsConnector>,
+    retry_policy: Option<RetryPolicy>,
+    recorder: Option<EventRecorder>,
+    state_cache: Option<StateCache>,
 }
 
 impl AriClient {
+    /// Client with default HTTP settings, see [builder](AriClient::builder) for customization.
     pub fn new(url: String, user: String, password: String) -> Self {
+        AriClient::with_http_client(url, user, password, reqwest::Client::new(), None)
+    }
+
+    /// Builder allowing to configure HTTP client (timeouts, proxy, TLS, ...) and websocket connection.
+    pub fn builder() -> AriClientBuilder {
+        AriClientBuilder::default()
+    }
+
+    fn with_http_client(
+        url: String,
+        user: String,
+        password: String,
+        http_client: reqwest::Client,
+        tls_connector: Option<TlsConnector>,
+    ) -> Self {
         AriClient {
             url,
             user,
@@ -70,133 +143,428 @@ impl AriClient {
             channel_var_set_sender: None,
             recording_started_sender: None,
             recording_finished_sender: None,
+            event_bus: broadcast::channel(EVENT_BUS_CAPACITY).0,
+            sender_policy: BackpressurePolicy::default(),
+            event_sinks: Arc::new(std::sync::Mutex::new(vec![])),
+            reconnect_policy: None,
+            connection_state_sender: None,
+            reconnect_hook: None,
+            shutdown: ShutdownHandle::default(),
+            keepalive: KeepaliveConfig::default(),
+            connection_options: Arc::new(watch::channel(ConnectionOptions::default()).0),
+            http_client,
+            tls_connector,
+            retry_policy: None,
+            recorder: None,
+            state_cache: None,
         }
     }
 
     pub fn set_stasis_start_sender(&mut self, sender: Option<Sender<StasisStart>>) {
-        self.stasis_start_sender = sender;
+        self.stasis_start_sender = sender.map(TypedSender::new);
     }
 
     pub fn set_channel_dtmf_received_sender(
         &mut self,
         sender: Option<Sender<ChannelDtmfReceived>>,
     ) {
-        self.channel_dtmf_received_sender = sender;
+        self.channel_dtmf_received_sender = sender.map(TypedSender::new);
     }
 
     pub fn set_channel_hangup_request_sender(
         &mut self,
         sender: Option<Sender<ChannelHangupRequest>>,
     ) {
-        self.channel_hangup_request_sender = sender;
+        self.channel_hangup_request_sender = sender.map(TypedSender::new);
     }
 
     pub fn set_stasis_end_sender(&mut self, sender: Option<Sender<StasisEnd>>) {
-        self.stasis_end_sender = sender;
+        self.stasis_end_sender = sender.map(TypedSender::new);
     }
 
     pub fn set_channel_talking_finished_sender(
         &mut self,
         sender: Option<Sender<ChannelTalkingFinished>>,
     ) {
-        self.channel_talking_finished_sender = sender;
+        self.channel_talking_finished_sender = sender.map(TypedSender::new);
     }
 
     pub fn set_channel_talking_started_sender(
         &mut self,
         sender: Option<Sender<ChannelTalkingStarted>>,
     ) {
-        self.channel_talking_started_sender = sender;
+        self.channel_talking_started_sender = sender.map(TypedSender::new);
     }
 
     pub fn set_channel_destroyed_sender(&mut self, sender: Option<Sender<ChannelDestroyed>>) {
-        self.channel_destroyed_sender = sender;
+        self.channel_destroyed_sender = sender.map(TypedSender::new);
     }
 
     pub fn set_playback_started_sender(&mut self, sender: Option<Sender<PlaybackStarted>>) {
-        self.playback_started_sender = sender;
+        self.playback_started_sender = sender.map(TypedSender::new);
     }
 
     pub fn set_playback_finished_sender(&mut self, sender: Option<Sender<PlaybackFinished>>) {
-        self.playback_finished_sender = sender;
+        self.playback_finished_sender = sender.map(TypedSender::new);
     }
 
     pub fn set_channel_state_change_sender(&mut self, sender: Option<Sender<ChannelStateChange>>) {
-        self.channel_state_change_sender = sender;
+        self.channel_state_change_sender = sender.map(TypedSender::new);
     }
 
     pub fn set_channel_var_set_sender(&mut self, sender: Option<Sender<ChannelVarset>>) {
-        self.channel_var_set_sender = sender;
+        self.channel_var_set_sender = sender.map(TypedSender::new);
     }
 
     pub fn set_recording_started_sender(&mut self, sender: Option<Sender<RecordingStarted>>) {
-        self.recording_started_sender = sender;
+        self.recording_started_sender = sender.map(TypedSender::new);
     }
 
     pub fn set_recording_finished_sender(&mut self, sender: Option<Sender<RecordingFinished>>) {
-        self.recording_finished_sender = sender;
+        self.recording_finished_sender = sender.map(TypedSender::new);
+    }
+
+    /// Subscribe to all ARI events received by this client (and its clones).
+    /// Unlike the typed senders above, any number of subscribers can be registered at any time,
+    /// even while ari_processing_loop is already running.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<AriEvent> {
+        self.event_bus.subscribe()
+    }
+
+    /// Subscribe to all ARI events with own queue of given capacity and backpressure policy.
+    /// Slow subscriber does not block websocket processing (unless [Block](BackpressurePolicy::Block) is used),
+    /// dropped events are reported in receiver's [stats](EventQueueReceiver::stats).
+    pub fn subscribe_events_with_policy(
+        &self,
+        capacity: usize,
+        policy: BackpressurePolicy,
+    ) -> EventQueueReceiver<AriEvent> {
+        let (sender, receiver) = event_queue(capacity, policy);
+        self.add_event_sink(Arc::new(sender));
+        receiver
+    }
+
+    /// Register custom destination of ARI events received by this client (and its clones).
+    pub fn add_event_sink(&self, sink: Arc<dyn EventSink>) {
+        self.event_sinks.lock().unwrap().push(sink);
+    }
+
+    /// Backpressure policy of typed senders (set_*_sender methods), applied when the sender receives its first event.
+    /// Default is [Unbounded](BackpressurePolicy::Unbounded), i.e. events are queued without blocking websocket processing.
+    /// Queue capacity equals to the capacity of the sender's channel.
+    pub fn set_sender_policy(&mut self, policy: BackpressurePolicy) {
+        self.sender_policy = policy;
+    }
+
+    /// Delivery metrics of all typed senders combined.
+    pub fn sender_stats(&self) -> DeliveryStats {
+        [
+            self.stasis_start_sender.as_ref().map(TypedSender::stats),
+            self.channel_dtmf_received_sender
+                .as_ref()
+                .map(TypedSender::stats),
+            self.channel_hangup_request_sender
+                .as_ref()
+                .map(TypedSender::stats),
+            self.stasis_end_sender.as_ref().map(TypedSender::stats),
+            self.channel_talking_finished_sender
+                .as_ref()
+                .map(TypedSender::stats),
+            self.channel_talking_started_sender
+                .as_ref()
+                .map(TypedSender::stats),
+            self.channel_destroyed_sender
+                .as_ref()
+                .map(TypedSender::stats),
+            self.playback_started_sender
+                .as_ref()
+                .map(TypedSender::stats),
+            self.playback_finished_sender
+                .as_ref()
+                .map(TypedSender::stats),
+            self.channel_state_change_sender
+                .as_ref()
+                .map(TypedSender::stats),
+            self.channel_var_set_sender.as_ref().map(TypedSender::stats),
+            self.recording_started_sender
+                .as_ref()
+                .map(TypedSender::stats),
+            self.recording_finished_sender
+                .as_ref()
+                .map(TypedSender::stats),
+        ]
+        .iter()
+        .flatten()
+        .fold(DeliveryStats::default(), |total, stats| total + *stats)
+    }
+
+    /// Enables automatic reconnection of the websocket. None (default) means
+    /// ari_processing_loop returns on first error or when the connection is closed.
+    pub fn set_reconnect_policy(&mut self, policy: Option<ReconnectPolicy>) {
+        self.reconnect_policy = policy;
+    }
+
+    pub fn set_connection_state_sender(&mut self, sender: Option<Sender<ConnectionState>>) {
+        self.connection_state_sender = sender;
+    }
+
+    /// Hook invoked after the websocket was reconnected, before event processing resumes.
+    /// Intended to resynchronize application state, e.g. by listing channels in Stasis.
+    pub fn set_reconnect_hook<F, Fut>(&mut self, hook: F)
+    where
+        F: Fn(AriClient) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.reconnect_hook = Some(Arc::new(move |client| Box::pin(hook(client))));
+    }
+
+    /// Websocket ping interval and dead connection detection settings.
+    pub fn set_keepalive(&mut self, keepalive: KeepaliveConfig) {
+        self.keepalive = keepalive;
+    }
+
+    /// Retry REST calls failing with transient errors. None (default) disables retrying.
+    pub fn set_retry_policy(&mut self, policy: Option<RetryPolicy>) {
+        self.retry_policy = policy;
+    }
+
+    /// Clone of the client using given retry policy, e.g. to disable retrying of a single call:
+    /// `client.with_retry_policy(None).hangup(channel_id).await`
+    pub fn with_retry_policy(&self, policy: Option<RetryPolicy>) -> AriClient {
+        let mut client = self.clone();
+        client.retry_policy = policy;
+        client
+    }
+
+    /// Record websocket messages and REST calls of this client (and its clones created afterwards)
+    /// to JSONL file, see [EventReplayer](crate::replay::EventReplayer). None (default) disables recording.
+    pub fn set_recorder(&mut self, recorder: Option<EventRecorder>) {
+        self.recorder = recorder;
+    }
+
+    /// Keep channels and bridges in given cache up to date from received events. Cache is updated before
+    /// the event is delivered to subscribers and resynchronized from REST API after the websocket is reconnected
+    /// (before reconnect hook is invoked). None (default) disables caching.
+    pub fn set_state_cache(&mut self, cache: Option<StateCache>) {
+        self.state_cache = cache;
+    }
+
+    pub fn state_cache(&self) -> Option<&StateCache> {
+        self.state_cache.as_ref()
+    }
+
+    /// Handle stopping ari_processing_loop of this client and all its clones.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Subscribe additional application on running connection.
+    /// Websocket is transparently reconnected with the updated list of applications.
+    pub fn add_application(&self, asterisk_app: &str) {
+        self.connection_options.send_if_modified(|options| {
+            if options.apps.iter().any(|app| app == asterisk_app) {
+                return false;
+            }
+            options.apps.push(asterisk_app.to_owned());
+            true
+        });
+    }
+
+    /// Unsubscribe application from running connection.
+    /// Websocket is transparently reconnected with the updated list of applications.
+    pub fn remove_application(&self, asterisk_app: &str) {
+        self.connection_options.send_if_modified(|options| {
+            let apps_count = options.apps.len();
+            options.apps.retain(|app| app != asterisk_app);
+            options.apps.len() != apps_count
+        });
+    }
+
+    /// Applications the websocket is currently (or will be) subscribed to.
+    pub fn applications(&self) -> Vec<String> {
+        self.connection_options.borrow().apps.clone()
     }
 
     /// connect to ARI signal stream websocket
+    /// and keep reconnecting according to reconnect policy (if any)
     pub async fn ari_processing_loop(&self, asterisk_apps: Vec<String>) -> Result<()> {
-        let ws_protocol = if self.url.starts_with("https://") {
-            "wss"
-        } else {
-            "ws"
-        };
+        self.ari_processing_loop_with_options(ConnectionOptions {
+            apps: asterisk_apps,
+            ..Default::default()
+        })
+        .await
+    }
 
-        let url = Url::parse(&self.url)?;
-        let hostname;
-        if let Some(host) = url.host_str() {
-            hostname = host;
-        } else {
-            return Err(Error::new(
-                StatusCode::BAD_REQUEST,
-                Some("unable to parse hostname".into()),
-            ));
-        }
+    /// same as ari_processing_loop, allows to specify additional connection options (e.g. subscribeAll)
+    pub async fn ari_processing_loop_with_options(&self, options: ConnectionOptions) -> Result<()> {
+        let result = self.run_processing_loop(options).await;
+        // events queued by sinks and typed senders are handed over before returning,
+        // i.e. nothing received before the shutdown is lost
+        self.flush_deliveries().await;
+        result
+    }
 
-        let portno;
-        if let Some(por) = url.port() {
-            portno = por;
-        } else {
-            return Err(Error::new(
-                StatusCode::BAD_REQUEST,
-                Some("unable to parse port".into()),
-            ));
-        }
+    async fn run_processing_loop(&self, options: ConnectionOptions) -> Result<()> {
+        self.connection_options.send_replace(options);
+        let mut options_receiver = self.connection_options.subscribe();
+        let mut shutdown_receiver = self.shutdown.subscribe();
+        let mut attempt = 0;
+        let mut connected_before = false;
 
-        let app_str = asterisk_apps.join(",");
-
-        // at the moment we are not supporting 'subscribeAll=true' option.
-        // // will be added once/if needed
-        let ws_url_str = format!(
-            "{}://{}:{}/ari/events?app={}&api_key={}:{}",
-            ws_protocol,
-            hostname,
-            portno,
-            app_str,
-            encode(&self.user),
-            encode(&self.password)
-        );
+        loop {
+            if self.shutdown.is_shutdown() {
+                info!("ari_processing_loop shut down");
+                return Ok(());
+            }
+
+            let result = match self.connect_websocket(&mut options_receiver).await {
+                Ok(ws_stream) => {
+                    attempt = 0;
+                    self.notify_connection_state(ConnectionState::Connected)
+                        .await;
+                    if connected_before {
+                        if let Some(cache) = &self.state_cache {
+                            let apps = self.connection_options.borrow().apps.clone();
+                            if let Err(err) = cache.resync(self, &apps).await {
+                                warn!("unable to resynchronize state cache {:?}", err);
+                            }
+                        }
+                        if let Some(hook) = &self.reconnect_hook {
+                            hook(self.clone()).await;
+                        }
+                    }
+                    connected_before = true;
+
+                    let result = self
+                        .process_websocket(ws_stream, &mut options_receiver)
+                        .await;
+                    self.notify_connection_state(ConnectionState::Disconnected)
+                        .await;
+                    if self.shutdown.is_shutdown() {
+                        info!("ari_processing_loop shut down");
+                        return result.map(|_| ());
+                    }
+                    match result {
+                        Ok(WsExit::OptionsChanged) => continue,
+                        Ok(WsExit::Closed) => Ok(()),
+                        Err(err) => Err(err),
+                    }
+                }
+                Err(err) => Err(err),
+            };
+
+            let policy = match &self.reconnect_policy {
+                Some(policy) => policy,
+                None => return result,
+            };
+
+            if let Err(err) = &result {
+                warn!("ari_processing_loop websocket error {:?}", err);
+            }
 
-        let ws_url = Url::parse(&ws_url_str)?;
+            attempt += 1;
+            if !policy.allows_attempt(attempt) {
+                error!(
+                    "ari_processing_loop giving up after {} attempts",
+                    attempt - 1
+                );
+                return result;
+            }
+
+            let delay = policy.delay(attempt);
+            info!(
+                "ari_processing_loop reconnecting in {:?} (attempt {})",
+                delay, attempt
+            );
+            self.notify_connection_state(ConnectionState::Reconnecting { attempt, delay })
+                .await;
+            tokio::select! {
+                _ = sleep(delay) => {}
+                _ = shutdown_receiver.changed() => {}
+            }
+        }
+    }
+
+    async fn connect_websocket(
+        &self,
+        options_receiver: &mut watch::Receiver<ConnectionOptions>,
+    ) -> Result<WsStream> {
+        let options = options_receiver.borrow_and_update().clone();
 
+        let ws_url = websocket_url(&self.url, &options)?;
         debug!("connecting to ws_url: {}", ws_url);
-        let (ws_stream, _) = connect_async(ws_url).await?;
 
-        let (mut ws_sender, mut ws_receiver) = ws_stream.split();
+        let ws_request = match options.auth {
+            WebsocketAuth::Header => {
+                let mut ws_request = ws_url.into_client_request()?;
+                let auth_header = WSHeaderValue::from_str(&self.get_auth_header())
+                    .map_err(|err| WSError::HttpFormat(err.into()))?;
+                ws_request
+                    .headers_mut()
+                    .insert("Authorization", auth_header);
+                ws_request
+            }
+            WebsocketAuth::ApiKey => {
+                // credentials must not be logged, i.e. appended only after debug! above
+                let mut ws_url = ws_url;
+                ws_url
+                    .query_pairs_mut()
+                    .append_pair("api_key", &format!("{}:{}", self.user, self.password));
+                ws_url.into_client_request()?
+            }
+        };
 
+        let connector = self.tls_connector.clone().map(Connector::NativeTls);
+        let (ws_stream, _) =
+            connect_async_tls_with_config(ws_request, None, false, connector).await?;
         debug!("websocket connected");
 
-        let mut interval = interval(Duration::from_millis(5000));
+        Ok(ws_stream)
+    }
+
+    /// process websocket messages until the connection is closed or connection options are changed
+    async fn process_websocket(
+        &self,
+        ws_stream: WsStream,
+        options_receiver: &mut watch::Receiver<ConnectionOptions>,
+    ) -> Result<WsExit> {
+        let (mut ws_sender, mut ws_receiver) = ws_stream.split();
+
+        // zero ping interval disables keepalive
+        let mut ping_interval = if self.keepalive.ping_interval.is_zero() {
+            None
+        } else {
+            Some(interval(self.keepalive.ping_interval))
+        };
+        // time of last message received from Asterisk and deadline for pending pong (if any)
+        let mut last_received = Instant::now();
+        let mut pong_deadline: Option<Instant> = None;
+
+        let mut shutdown_receiver = self.shutdown.subscribe();
+        // shutdown could have been requested while connecting
+        let mut close_deadline = if self.shutdown.is_shutdown() {
+            Some(self.send_close_frame(&mut ws_sender).await)
+        } else {
+            None
+        };
 
         loop {
             tokio::select! {
                 msg = ws_receiver.next() => {
                     match msg {
                         Some(msg) => {
-                            let msg = msg?;
+                            let msg = match msg {
+                                Ok(msg) => msg,
+                                // Asterisk might drop the connection during close handshake
+                                Err(err) if close_deadline.is_some() => {
+                                    debug!("websocket error during close handshake {:?}", err);
+                                    break;
+                                }
+                                Err(err) => return Err(err.into()),
+                            };
+                            // any frame proves the connection is alive, not only pong
+                            last_received = Instant::now();
+                            pong_deadline = None;
                             match msg {
                                     WSMessage::Close(close_frame) => {
                                         info!(
@@ -214,6 +582,9 @@ impl AriClient {
                                             "asterisk signal event received: {:#?}",
                                             string_msg
                                         );
+                                        if let Some(recorder) = &self.recorder {
+                                            recorder.record_event(&string_msg);
+                                        }
                                         let ari_event =
                                             serde_json::from_str::<AriEvent>(&string_msg);
                                         if let Err(deser_err) = ari_event {
@@ -224,99 +595,7 @@ impl AriClient {
                                         } else {
                                             let ari_event = ari_event.unwrap();
                                             trace!("ari_event: {:#?}", ari_event);
-                                            match ari_event {
-                                                AriEvent::StasisStart(event) => {
-                                                    if let Some(sender) = &self.stasis_start_sender {
-                                                        if let Err(send_err) = sender.send(event.clone()).await {
-                                                            error!("ari_processing_loop StasisStart sending error {:?}: ", send_err);
-                                                        }
-                                                    }
-                                                }
-                                                AriEvent::ChannelDtmfReceived(event) => {
-                                                    if let Some(sender) = &self.channel_dtmf_received_sender {
-                                                        if let Err(send_err) = sender.send(event.clone()).await {
-                                                            error!("ari_processing_loop ChannelDtmfReceived sending error {:?}: ", send_err);
-                                                        }
-                                                    }
-                                                }
-                                                AriEvent::ChannelHangupRequest(event) => {
-                                                    if let Some(sender) = &self.channel_hangup_request_sender {
-                                                        if let Err(send_err) = sender.send(event.clone()).await {
-                                                            error!("ari_processing_loop ChannelHangupRequest sending error {:?}: ", send_err);
-                                                        }
-                                                    }
-                                                }
-                                                AriEvent::StasisEnd(event) => {
-                                                    if let Some(sender) = &self.stasis_end_sender {
-                                                        if let Err(send_err) = sender.send(event.clone()).await {
-                                                            error!("ari_processing_loop StasisEnd sending error {:?}: ", send_err);
-                                                        }
-                                                    }
-                                                }
-                                                AriEvent::ChannelTalkingFinished(event) => {
-                                                    if let Some(sender) = &self.channel_talking_finished_sender {
-                                                        if let Err(send_err) = sender.send(event.clone()).await {
-                                                            error!("ari_processing_loop ChannelTalkingFinished sending error {:?}: ", send_err);
-                                                        }
-                                                    }
-                                                }
-                                                AriEvent::ChannelTalkingStarted(event) => {
-                                                    if let Some(sender) = &self.channel_talking_started_sender {
-                                                        if let Err(send_err) = sender.send(event.clone()).await {
-                                                            error!("ari_processing_loop ChannelTalkingStarted sending error {:?}: ", send_err);
-                                                        }
-                                                    }
-                                                }
-                                                AriEvent::ChannelDestroyed(event) => {
-                                                    if let Some(sender) = &self.channel_destroyed_sender {
-                                                        if let Err(send_err) = sender.send(event.clone()).await {
-                                                            error!("ari_processing_loop ChannelDestroyed sending error {:?}: ", send_err);
-                                                        }
-                                                    }
-                                                }
-                                                AriEvent::PlaybackStarted(event) => {
-                                                    if let Some(sender) = &self.playback_started_sender {
-                                                        if let Err(send_err) = sender.send(event.clone()).await {
-                                                            error!("ari_processing_loop PlaybackStarted sending error {:?}: ", send_err);
-                                                        }
-                                                    }
-                                                }
-                                                AriEvent::PlaybackFinished(event) => {
-                                                    if let Some(sender) = &self.playback_finished_sender {
-                                                        if let Err(send_err) = sender.send(event.clone()).await {
-                                                            error!("ari_processing_loop PlaybackFinished sending error {:?}: ", send_err);
-                                                        }
-                                                    }
-                                                }
-                                                AriEvent::ChannelStateChange(event) => {
-                                                    if let Some(sender) = &self.channel_state_change_sender {
-                                                        if let Err(send_err) = sender.send(event.clone()).await {
-                                                            error!("ari_processing_loop ChannelStateChange sending error {:?}: ", send_err);
-                                                        }
-                                                    }
-                                                }
-                                                AriEvent::ChannelVarset(event) => {
-                                                    if let Some(sender) = &self.channel_var_set_sender {
-                                                        if let Err(send_err) = sender.send(event.clone()).await {
-                                                            error!("ari_processing_loop ChannelVarset sending error {:?}: ", send_err);
-                                                        }
-                                                    }
-                                                }
-                                                AriEvent::RecordingStarted(event) => {
-                                                    if let Some(sender) = &self.recording_started_sender {
-                                                        if let Err(send_err) = sender.send(event.clone()).await {
-                                                            error!("ari_processing_loop RecordingStarted sending error {:?}: ", send_err);
-                                                        }
-                                                    }
-                                                }
-                                                AriEvent::RecordingFinished(event) => {
-                                                    if let Some(sender) = &self.recording_finished_sender {
-                                                        if let Err(send_err) = sender.send(event.clone()).await {
-                                                            error!("ari_processing_loop RecordingFinished sending error {:?}: ", send_err);
-                                                        }
-                                                    }
-                                                }
-                                            }
+                                            self.dispatch_event(ari_event).await;
                                         }
                                     }
                                     _ => {
@@ -330,17 +609,203 @@ impl AriClient {
                         None => break,
                     }
                 }
-                _ = interval.tick() => {
-                    // every 5 seconds we are sending ping to keep connection alive
+                _ = options_receiver.changed(), if close_deadline.is_none() => {
+                    info!("connection options changed, reconnecting websocket");
+                    let _ = ws_sender.send(WSMessage::Close(None)).await;
+                    return Ok(WsExit::OptionsChanged);
+                }
+                _ = shutdown_receiver.changed(), if close_deadline.is_none() => {
+                    close_deadline = Some(self.send_close_frame(&mut ws_sender).await);
+                }
+                _ = sleep_until(close_deadline.unwrap_or_else(Instant::now)), if close_deadline.is_some() => {
+                    warn!("websocket close not acknowledged within {:?}", CLOSE_TIMEOUT);
+                    break;
+                }
+                _ = sleep_until(pong_deadline.unwrap_or_else(Instant::now)), if pong_deadline.is_some() && close_deadline.is_none() => {
+                    warn!(
+                        "ari connection pong not received, last message received {:?} ago",
+                        last_received.elapsed()
+                    );
+                    return Err(Error::KeepaliveTimeout(last_received.elapsed()));
+                }
+                _ = tick(&mut ping_interval), if close_deadline.is_none() => {
+                    // we are periodically sending ping to keep connection alive
                     // https://rust-lang-nursery.github.io/rust-cookbook/algorithms/randomness.html
                     let random_bytes = rand::thread_rng().gen::<[u8; 32]>().to_vec();
                     let _ = ws_sender.send(WSMessage::Ping(random_bytes)).await;
                     debug!("ari connection ping sent");
+                    if pong_deadline.is_none() {
+                        pong_deadline = self.keepalive.pong_timeout.map(|timeout| Instant::now() + timeout);
+                    }
                 }
             }
         }
 
-        Ok(())
+        Ok(WsExit::Closed)
+    }
+
+    /// initiate websocket close handshake, returns deadline for close acknowledgement
+    async fn send_close_frame(&self, ws_sender: &mut SplitSink<WsStream, WSMessage>) -> Instant {
+        info!("shutdown requested, closing websocket");
+        let close_frame = CloseFrame {
+            code: CloseCode::Normal,
+            reason: "shutdown".into(),
+        };
+        if let Err(err) = ws_sender.send(WSMessage::Close(Some(close_frame))).await {
+            warn!("unable to send websocket close frame {:?}", err);
+        }
+        Instant::now() + CLOSE_TIMEOUT
+    }
+
+    async fn notify_connection_state(&self, state: ConnectionState) {
+        if let Some(sender) = &self.connection_state_sender {
+            if let Err(send_err) = sender.send(state).await {
+                error!(
+                    "ari_processing_loop ConnectionState sending error {:?}: ",
+                    send_err
+                );
+            }
+        }
+    }
+
+    /// wait until registered sinks and typed senders deliver queued events to their consumers
+    async fn flush_deliveries(&self) {
+        let sinks = self.event_sinks.lock().unwrap().clone();
+        for sink in sinks {
+            sink.flush().await;
+        }
+
+        tokio::join!(
+            flush_sender(&self.stasis_start_sender),
+            flush_sender(&self.channel_dtmf_received_sender),
+            flush_sender(&self.channel_hangup_request_sender),
+            flush_sender(&self.stasis_end_sender),
+            flush_sender(&self.channel_talking_finished_sender),
+            flush_sender(&self.channel_talking_started_sender),
+            flush_sender(&self.channel_destroyed_sender),
+            flush_sender(&self.playback_started_sender),
+            flush_sender(&self.playback_finished_sender),
+            flush_sender(&self.channel_state_change_sender),
+            flush_sender(&self.channel_var_set_sender),
+            flush_sender(&self.recording_started_sender),
+            flush_sender(&self.recording_finished_sender),
+        );
+    }
+
+    /// update state cache and deliver ARI event to the event bus, registered sinks and respective typed sender (if any)
+    pub(crate) async fn dispatch_event(&self, ari_event: AriEvent) {
+        if let Some(cache) = &self.state_cache {
+            cache.apply(&ari_event);
+        }
+
+        // error only means there is no subscriber at the moment
+        let _ = self.event_bus.send(ari_event.clone());
+
+        let sinks = {
+            let mut sinks = self.event_sinks.lock().unwrap();
+            sinks.retain(|sink| !sink.is_closed());
+            sinks.clone()
+        };
+        for sink in sinks {
+            sink.deliver(&ari_event).await;
+        }
+
+        match ari_event {
+            AriEvent::StasisStart(event) => {
+                if let Some(sender) = &self.stasis_start_sender {
+                    sender
+                        .deliver(event, self.sender_policy, "StasisStart")
+                        .await;
+                }
+            }
+            AriEvent::ChannelDtmfReceived(event) => {
+                if let Some(sender) = &self.channel_dtmf_received_sender {
+                    sender
+                        .deliver(event, self.sender_policy, "ChannelDtmfReceived")
+                        .await;
+                }
+            }
+            AriEvent::ChannelHangupRequest(event) => {
+                if let Some(sender) = &self.channel_hangup_request_sender {
+                    sender
+                        .deliver(event, self.sender_policy, "ChannelHangupRequest")
+                        .await;
+                }
+            }
+            AriEvent::StasisEnd(event) => {
+                if let Some(sender) = &self.stasis_end_sender {
+                    sender.deliver(event, self.sender_policy, "StasisEnd").await;
+                }
+            }
+            AriEvent::ChannelTalkingFinished(event) => {
+                if let Some(sender) = &self.channel_talking_finished_sender {
+                    sender
+                        .deliver(event, self.sender_policy, "ChannelTalkingFinished")
+                        .await;
+                }
+            }
+            AriEvent::ChannelTalkingStarted(event) => {
+                if let Some(sender) = &self.channel_talking_started_sender {
+                    sender
+                        .deliver(event, self.sender_policy, "ChannelTalkingStarted")
+                        .await;
+                }
+            }
+            AriEvent::ChannelDestroyed(event) => {
+                if let Some(sender) = &self.channel_destroyed_sender {
+                    sender
+                        .deliver(event, self.sender_policy, "ChannelDestroyed")
+                        .await;
+                }
+            }
+            AriEvent::PlaybackStarted(event) => {
+                if let Some(sender) = &self.playback_started_sender {
+                    sender
+                        .deliver(event, self.sender_policy, "PlaybackStarted")
+                        .await;
+                }
+            }
+            AriEvent::PlaybackFinished(event) => {
+                if let Some(sender) = &self.playback_finished_sender {
+                    sender
+                        .deliver(event, self.sender_policy, "PlaybackFinished")
+                        .await;
+                }
+            }
+            AriEvent::ChannelStateChange(event) => {
+                if let Some(sender) = &self.channel_state_change_sender {
+                    sender
+                        .deliver(event, self.sender_policy, "ChannelStateChange")
+                        .await;
+                }
+            }
+            AriEvent::ChannelVarset(event) => {
+                if let Some(sender) = &self.channel_var_set_sender {
+                    sender
+                        .deliver(event, self.sender_policy, "ChannelVarset")
+                        .await;
+                }
+            }
+            AriEvent::RecordingStarted(event) => {
+                if let Some(sender) = &self.recording_started_sender {
+                    sender
+                        .deliver(event, self.sender_policy, "RecordingStarted")
+                        .await;
+                }
+            }
+            AriEvent::RecordingFinished(event) => {
+                if let Some(sender) = &self.recording_finished_sender {
+                    sender
+                        .deliver(event, self.sender_policy, "RecordingFinished")
+                        .await;
+                }
+            }
+            // bridge events are delivered only to the event bus, sinks and handlers
+            AriEvent::BridgeCreated(_)
+            | AriEvent::BridgeDestroyed(_)
+            | AriEvent::ChannelEnteredBridge(_)
+            | AriEvent::ChannelLeftBridge(_) => {}
+        }
     }
 
     #[allow(deprecated)]
@@ -362,18 +827,127 @@ impl AriClient {
 
         Ok(headers)
     }
-}
 
-macro_rules! eval_status_code {
-    ($status_real:ident, $status_expected:expr, $body_str:expr) => {
-        if $status_real != $status_expected {
-            return if let Some(some_body) = $body_str {
-                Err(Error::new($status_real, Some(some_body)))
-            } else {
-                Err(Error::new($status_real, None))
+    /// sends REST request to ARI, any status code other than expected one results in Error::Api
+    async fn send_request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<String>,
+        expected_status: StatusCode,
+    ) -> Result<Response> {
+        let mut attempt = 1;
+        loop {
+            let mut req = self
+                .http_client
+                .request(method.clone(), format!("{}{}", self.url, path))
+                .headers(self.get_common_headers()?);
+            if let Some(body) = &body {
+                trace!("req body: {}", body);
+                req = req.body(body.clone());
+            }
+
+            let retry_policy = self
+                .retry_policy
+                .as_ref()
+                .filter(|policy| policy.allows_retry(&method, path, attempt));
+
+            let resp = req.send().await;
+            if let Some(recorder) = &self.recorder {
+                let outcome = resp.as_ref().map(|resp| resp.status());
+                recorder.record_request(&method, path, body.as_deref(), outcome);
+            }
+            let resp = match resp {
+                Ok(resp) => resp,
+                Err(err) => match retry_policy {
+                    Some(policy) if policy.is_retryable_error(&err) => {
+                        let backoff = policy.backoff(attempt);
+                        warn!(
+                            "{} {} failed (attempt {}): {}, retrying in {:?}",
+                            method, path, attempt, err, backoff
+                        );
+                        sleep(backoff).await;
+                        attempt += 1;
+                        continue;
+                    }
+                    _ => return Err(err.into()),
+                },
             };
+            let status = resp.status();
+            trace!("{} {} -> {}", method, path, status);
+
+            if status != expected_status {
+                if let Some(policy) =
+                    retry_policy.filter(|policy| policy.is_retryable_status(status))
+                {
+                    let backoff = policy.backoff(attempt);
+                    warn!(
+                        "{} {} -> {} (attempt {}), retrying in {:?}",
+                        method, path, status, attempt, backoff
+                    );
+                    sleep(backoff).await;
+                    attempt += 1;
+                    continue;
+                }
+                let body_str = resp.text().await.ok().filter(|body| !body.is_empty());
+                return Err(Error::api(method, path, status, body_str));
+            }
+            return Ok(resp);
         }
+    }
+}
+
+/// next tick of the interval, never completes when there is no interval
+async fn tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// flush of typed sender if it is set
+async fn flush_sender<T: Send + 'static>(sender: &Option<TypedSender<T>>) {
+    if let Some(sender) = sender {
+        sender.flush().await;
+    }
+}
+
+/// derives websocket (events) URL from ARI base URL keeping path prefix, port and host as they are
+fn websocket_url(base_url: &str, options: &ConnectionOptions) -> Result<Url> {
+    let mut url = Url::parse(base_url)?;
+
+    let ws_scheme = match url.scheme() {
+        "http" | "ws" => "ws",
+        "https" | "wss" => "wss",
+        scheme => return Err(Error::Config(format!("unsupported url scheme {}", scheme))),
     };
+    if url.host().is_none() {
+        return Err(Error::Config("unable to parse hostname".into()));
+    }
+    // switching between special schemes (http/https/ws/wss) never fails
+    let _ = url.set_scheme(ws_scheme);
+
+    let ws_path = format!("{}/events", url.path().trim_end_matches('/'));
+    url.set_path(&ws_path);
+    url.set_fragment(None);
+    url.set_query(None);
+    {
+        let mut query = url.query_pairs_mut();
+        query.append_pair("app", &options.apps.join(","));
+        if options.subscribe_all {
+            query.append_pair("subscribeAll", "true");
+        }
+    }
+
+    Ok(url)
+}
+
+impl Ari for AriClient {
+    fn subscribe_events(&self) -> broadcast::Receiver<AriEvent> {
+        AriClient::subscribe_events(self)
+    }
 }
 
 #[async_trait]
@@ -385,41 +959,30 @@ impl ApplicationsAPI for AriClient {
         application_name: &str,
         filter: Option<serde_json::Value>,
     ) -> Result<String> {
-        Err(Error::new(StatusCode::NOT_IMPLEMENTED, None))
+        Err(Error::Unsupported("filter"))
     }
 
     /// Get details of an application.
     async fn get(&self, application_name: &str) -> Result<Application> {
-        let resp = HTTP_CLIENT
-            .get(format!("{}/applications/{}", self.url, application_name))
-            .headers(self.get_common_headers()?)
-            .send()
+        let path = format!("/applications/{}", encode_segment(application_name));
+        let resp = self
+            .send_request(Method::GET, &path, None, StatusCode::OK)
             .await?;
-
-        let status = resp.status();
-        let body_str = resp.text().await?;
-        eval_status_code!(status, StatusCode::OK, Some(body_str));
-        Ok(serde_json::from_str(&body_str)?)
+        Ok(serde_json::from_str(&resp.text().await?)?)
     }
 
     /// List all applications.
     async fn list(&self) -> Result<Vec<Application>> {
-        let resp = HTTP_CLIENT
-            .get(format!("{}/applications", self.url))
-            .headers(self.get_common_headers()?)
-            .send()
+        let resp = self
+            .send_request(Method::GET, "/applications", None, StatusCode::OK)
             .await?;
-
-        let status = resp.status();
-        let body_str = resp.text().await?;
-        eval_status_code!(status, StatusCode::OK, Some(body_str));
-        Ok(serde_json::from_str(&body_str)?)
+        Ok(serde_json::from_str(&resp.text().await?)?)
     }
 
     /// Subscribe an application to a event source.
     #[allow(unused_variables)]
     async fn subscribe(&self, application_name: &str, event_source: Vec<String>) -> Result<String> {
-        Err(Error::new(StatusCode::NOT_IMPLEMENTED, None))
+        Err(Error::Unsupported("subscribe"))
     }
 
     /// Unsubscribe an application from an event source.
@@ -429,21 +992,91 @@ impl ApplicationsAPI for AriClient {
         application_name: &str,
         event_source: Vec<String>,
     ) -> Result<String> {
-        Err(Error::new(StatusCode::NOT_IMPLEMENTED, None))
+        Err(Error::Unsupported("unsubscribe"))
+    }
+}
+
+#[async_trait]
+impl BridgesAPI for AriClient {
+    async fn list_bridges(&self) -> Result<Vec<Bridge>> {
+        let resp = self
+            .send_request(Method::GET, "/bridges", None, StatusCode::OK)
+            .await?;
+        Ok(serde_json::from_str(&resp.text().await?)?)
+    }
+
+    async fn get_bridge(&self, bridge_id: &str) -> Result<Bridge> {
+        let path = format!("/bridges/{}", encode_segment(bridge_id));
+        let resp = self
+            .send_request(Method::GET, &path, None, StatusCode::OK)
+            .await?;
+        Ok(serde_json::from_str(&resp.text().await?)?)
     }
 }
 
 #[async_trait]
 impl ChannelsAPI for AriClient {
-    async fn answer(&self, channel_id: &str) -> Result<()> {
-        let resp = HTTP_CLIENT
-            .post(format!("{}/channels/{}/answer", self.url, channel_id))
-            .headers(self.get_common_headers()?)
-            .send()
+    async fn list_channels(&self) -> Result<Vec<Channel>> {
+        let resp = self
+            .send_request(Method::GET, "/channels", None, StatusCode::OK)
             .await?;
+        Ok(serde_json::from_str(&resp.text().await?)?)
+    }
 
-        let status = resp.status();
-        eval_status_code!(status, StatusCode::NO_CONTENT, None);
+    async fn originate(&self, endpoint: &str, options: OriginateOptions) -> Result<Channel> {
+        let req_body = serde_json::to_string(&OriginateRequest {
+            endpoint,
+            extension: options.extension.as_deref(),
+            context: options.context.as_deref(),
+            priority: options.priority,
+            label: options.label.as_deref(),
+            app: options.app.as_deref(),
+            app_args: options.app_args.as_deref(),
+            caller_id: options.caller_id.as_deref(),
+            timeout: options.timeout,
+            channel_id: options.channel_id.as_deref(),
+            other_channel_id: options.other_channel_id.as_deref(),
+            originator: options.originator.as_deref(),
+            formats: options.formats.as_deref(),
+            variables: &options.variables,
+        })?;
+        let resp = self
+            .send_request(Method::POST, "/channels", Some(req_body), StatusCode::OK)
+            .await?;
+        Ok(serde_json::from_str(&resp.text().await?)?)
+    }
+
+    async fn create_channel(
+        &self,
+        endpoint: &str,
+        app: &str,
+        options: CreateChannelOptions,
+    ) -> Result<Channel> {
+        let req_body = serde_json::to_string(&CreateChannelRequest {
+            endpoint,
+            app,
+            app_args: options.app_args.as_deref(),
+            channel_id: options.channel_id.as_deref(),
+            other_channel_id: options.other_channel_id.as_deref(),
+            originator: options.originator.as_deref(),
+            formats: options.formats.as_deref(),
+            variables: &options.variables,
+        })?;
+        let resp = self
+            .send_request(
+                Method::POST,
+                "/channels/create",
+                Some(req_body),
+                StatusCode::OK,
+            )
+            .await?;
+        Ok(serde_json::from_str(&resp.text().await?)?)
+    }
+
+    async fn answer(&self, channel_id: &str) -> Result<()> {
+        let path = format!("/channels/{}/answer", encode_segment(channel_id));
+        self.send_request(Method::POST, &path, None, StatusCode::NO_CONTENT)
+            .await?;
         Ok(())
     }
 
@@ -451,301 +1084,210 @@ impl ChannelsAPI for AriClient {
         &self,
         channel_id: &str,
         media: &str,
-        _playback_id: Option<String>,
-        _lang: Option<String>,
-        _offsetms: Option<usize>,
-        _skipms: Option<usize>,
+        playback_id: Option<String>,
+        lang: Option<String>,
+        offsetms: Option<usize>,
+        skipms: Option<usize>,
     ) -> Result<Playback> {
-        // so far we are not supporting optional parameters
-        let req_body = format!(
-            r#"
-            {{
-                "channelId": "{_channel_id_}",
-                "media": "{_media_}"
-            }}
-            "#,
-            _channel_id_ = channel_id,
-            _media_ = media,
-        );
-
-        let resp = HTTP_CLIENT
-            .post(format!("{}/channels/{}/play", self.url, channel_id))
-            .headers(self.get_common_headers()?)
-            .body(req_body)
-            .send()
+        let req_body = serde_json::to_string(&PlayRequest {
+            media,
+            playback_id: playback_id.as_deref(),
+            lang: lang.as_deref(),
+            offsetms,
+            skipms,
+        })?;
+
+        let path = format!("/channels/{}/play", encode_segment(channel_id));
+        let resp = self
+            .send_request(Method::POST, &path, Some(req_body), StatusCode::CREATED)
             .await?;
-
-        let status = resp.status();
-        let body_str = resp.text().await?;
-        eval_status_code!(status, StatusCode::CREATED, Some(body_str));
-        Ok(serde_json::from_str(&body_str)?)
+        Ok(serde_json::from_str(&resp.text().await?)?)
     }
 
     async fn stop_play(&self, playback_id: &str) -> Result<()> {
-        let resp = HTTP_CLIENT
-            .delete(format!("{}/playbacks/{}", self.url, playback_id))
-            .headers(self.get_common_headers()?)
-            .send()
+        let path = format!("/playbacks/{}", encode_segment(playback_id));
+        self.send_request(Method::DELETE, &path, None, StatusCode::NO_CONTENT)
             .await?;
-
-        let status = resp.status();
-        eval_status_code!(status, StatusCode::NO_CONTENT, None);
         Ok(())
     }
 
     async fn get_variable(&self, channel_id: &str, var_name: &str) -> Result<String> {
-        let resp = HTTP_CLIENT
-            .get(format!(
-                "{}/channels/{}/variable?variable={}",
-                self.url, channel_id, var_name
-            ))
-            .headers(self.get_common_headers()?)
-            .send()
+        let path = with_query(
+            format!("/channels/{}/variable", encode_segment(channel_id)),
+            &GetVariableQuery { variable: var_name },
+        )?;
+        let resp = self
+            .send_request(Method::GET, &path, None, StatusCode::OK)
             .await?;
 
-        let status = resp.status();
-        let body_str = resp.text().await?;
-        eval_status_code!(status, StatusCode::OK, Some(body_str));
-
-        let variable = serde_json::from_str::<Variable>(&body_str)?;
+        let variable = serde_json::from_str::<Variable>(&resp.text().await?)?;
         Ok(variable.value)
     }
 
     async fn set_variable(&self, channel_id: &str, var_name: &str, var_value: &str) -> Result<()> {
-        let resp = HTTP_CLIENT
-            .post(format!(
-                "{}/channels/{}/variable?variable={}&value={}",
-                self.url, channel_id, var_name, var_value
-            ))
-            .headers(self.get_common_headers()?)
-            .send()
+        let path = with_query(
+            format!("/channels/{}/variable", encode_segment(channel_id)),
+            &SetVariableQuery {
+                variable: var_name,
+                value: var_value,
+            },
+        )?;
+        self.send_request(Method::POST, &path, None, StatusCode::NO_CONTENT)
             .await?;
-
-        let status = resp.status();
-        let body_str = resp.text().await?;
-
-        eval_status_code!(status, StatusCode::NO_CONTENT, Some(body_str));
         Ok(())
     }
 
     async fn hangup(&self, channel_id: &str) -> Result<()> {
-        let resp = HTTP_CLIENT
-            .delete(format!("{}/channels/{}", self.url, channel_id))
-            .headers(self.get_common_headers()?)
-            .send()
+        let path = format!("/channels/{}", encode_segment(channel_id));
+        self.send_request(Method::DELETE, &path, None, StatusCode::NO_CONTENT)
             .await?;
-
-        let status = resp.status();
-        let body_str = resp.text().await?;
-        eval_status_code!(status, StatusCode::NO_CONTENT, Some(body_str));
         Ok(())
     }
 
     async fn continue_in_dialplan(&self, channel_id: &str) -> Result<()> {
-        let resp = HTTP_CLIENT
-            .post(format!("{}/channels/{}/continue", self.url, channel_id))
-            .headers(self.get_common_headers()?)
-            .send()
+        let path = format!("/channels/{}/continue", encode_segment(channel_id));
+        self.send_request(Method::POST, &path, None, StatusCode::NO_CONTENT)
             .await?;
-
-        let status = resp.status();
-        let body_str = resp.text().await?;
-        eval_status_code!(status, StatusCode::NO_CONTENT, Some(body_str));
         Ok(())
     }
 
-    async fn snoop(
-        &self,
-        channel_id: &str,
-        app: &str,
-        spy: Option<Direction>,
-        whisper: Option<Direction>,
-    ) -> Result<Channel> {
-        let req_body = format!(
-            r#"
-            {{
-                "app": "{_app_name_}",
-                "spy": "{_spy_}",
-                "whisper": "{_whisper_}"
-            }}
-            "#,
-            _app_name_ = app,
-            _spy_ = spy.unwrap_or_default(),
-            _whisper_ = whisper.unwrap_or_default()
-        );
-
-        let req = HTTP_CLIENT
-            .post(format!("{}/channels/{}/snoop", self.url, channel_id))
-            .headers(self.get_common_headers()?)
-            .body(req_body.clone());
-        trace!("req: {req:#?}");
-        trace!("req body: {}", req_body);
-        trace!("url: {:#?}", self.url);
-
-        let resp = req.send().await?;
-
-        trace!("response: {:#?}", resp);
-
-        let status = resp.status();
-        trace!("status: {:#?}", status);
+    async fn snoop(&self, channel_id: &str, app: &str, options: SnoopOptions) -> Result<Channel> {
+        let req_body = serde_json::to_string(&SnoopRequest {
+            app,
+            spy: &options.spy,
+            whisper: &options.whisper,
+            app_args: options.app_args.as_deref(),
+            snoop_id: options.snoop_id.as_deref(),
+        })?;
+
+        let path = format!("/channels/{}/snoop", encode_segment(channel_id));
+        let resp = self
+            .send_request(Method::POST, &path, Some(req_body), StatusCode::OK)
+            .await?;
 
         let body_str = resp.text().await?;
         trace!("text: {:#?}", body_str);
 
-        eval_status_code!(status, StatusCode::OK, Some(body_str));
-
         let res_chan = serde_json::from_str(&body_str)?;
         Ok(res_chan)
     }
 
-    async fn record(
-        &self,
-        channel_id: &str,
-        filepath: Option<&str>,
-        audio_format: Option<&str>,
-        terminate_on: Option<&str>,
-        max_duration: Option<usize>,
-        max_silence: Option<usize>,
-        if_exists: Option<&str>,
-        beep: Option<bool>,
-    ) -> Result<()> {
-        let req_body = format!(
-            r#"
-            {{
-                "name": "{_filepath_}",
-                "format": "{_audio_format_}",
-                "terminateOn": "{_terminate_on_}",
-                "maxDuration": {_max_duration_},
-                "maxSilence": {_max_silence_},
-                "ifExists": "{_if_exists_}",
-                "beep": {_beep_}
-            }}
-            "#,
-            _filepath_ = filepath.unwrap_or(channel_id),
-            _audio_format_ = audio_format.unwrap_or("wav"),
-            _terminate_on_ = terminate_on.unwrap_or("none"),
-            _max_duration_ = max_duration.unwrap_or(0),
-            _max_silence_ = max_silence.unwrap_or(0),
-            _if_exists_ = if_exists.unwrap_or("fail"),
-            _beep_ = beep.unwrap_or(false),
-        );
-        let resp = HTTP_CLIENT
-            .post(format!("{}/channels/{}/record", self.url, channel_id))
-            .headers(self.get_common_headers()?)
-            .body(req_body)
-            .send()
+    async fn record(&self, channel_id: &str, options: RecordOptions) -> Result<Recording> {
+        let req_body = serde_json::to_string(&RecordRequest {
+            name: options.name.as_deref().unwrap_or(channel_id),
+            format: &options.format,
+            terminate_on: &options.terminate_on,
+            max_duration_seconds: options.max_duration_seconds,
+            max_silence_seconds: options.max_silence_seconds,
+            if_exists: &options.if_exists,
+            beep: options.beep,
+        })?;
+        let path = format!("/channels/{}/record", encode_segment(channel_id));
+        let resp = self
+            .send_request(Method::POST, &path, Some(req_body), StatusCode::CREATED)
             .await?;
-
-        let status = resp.status();
-        let body_str = resp.text().await?;
-        eval_status_code!(status, StatusCode::CREATED, Some(body_str));
-        Ok(())
+        Ok(serde_json::from_str(&resp.text().await?)?)
     }
 }
 
 #[async_trait]
 impl RecordingsAPI for AriClient {
     async fn get_recording(&self, recording_name: &str) -> Result<Vec<u8>> {
-        let recording_name = utf8_percent_encode(recording_name, NON_ALPHANUMERIC);
-        let resp = HTTP_CLIENT
-            .get(format!(
-                "{}/recordings/stored/{}/file",
-                self.url, recording_name
-            ))
-            .headers(self.get_common_headers()?)
-            .send()
+        let path = format!("/recordings/stored/{}/file", encode_segment(recording_name));
+        let resp = self
+            .send_request(Method::GET, &path, None, StatusCode::OK)
             .await?;
-        let status = resp.status();
-        let body_bytes = resp.bytes().await?;
-
-        eval_status_code!(status, StatusCode::OK, Some(format!("{body_bytes:#?}")));
-        Ok(body_bytes.to_vec())
+        Ok(resp.bytes().await?.to_vec())
     }
+
     async fn stop_recording(&self, recording_name: &str) -> Result<()> {
-        let resp = HTTP_CLIENT
-            .post(format!(
-                "{}/recordings/live/{}/stop",
-                self.url, recording_name
-            ))
-            .headers(self.get_common_headers()?)
-            .send()
+        let path = format!("/recordings/live/{}/stop", encode_segment(recording_name));
+        self.send_request(Method::POST, &path, None, StatusCode::NO_CONTENT)
             .await?;
-
-        let status = resp.status();
-        let body_str = resp.text().await?;
-        eval_status_code!(status, StatusCode::NO_CONTENT, Some(body_str));
         Ok(())
     }
 
     async fn pause_recording(&self, recording_name: &str) -> Result<()> {
-        let resp = HTTP_CLIENT
-            .post(format!(
-                "{}/recordings/live/{}/pause",
-                self.url, recording_name
-            ))
-            .headers(self.get_common_headers()?)
-            .send()
+        let path = format!("/recordings/live/{}/pause", encode_segment(recording_name));
+        self.send_request(Method::POST, &path, None, StatusCode::NO_CONTENT)
             .await?;
-
-        let status = resp.status();
-        eval_status_code!(status, StatusCode::NO_CONTENT, None);
         Ok(())
     }
 
     async fn unpause_recording(&self, recording_name: &str) -> Result<()> {
-        let resp = HTTP_CLIENT
-            .delete(format!(
-                "{}/recordings/live/{}/pause",
-                self.url, recording_name
-            ))
-            .headers(self.get_common_headers()?)
-            .send()
+        let path = format!("/recordings/live/{}/pause", encode_segment(recording_name));
+        self.send_request(Method::DELETE, &path, None, StatusCode::NO_CONTENT)
             .await?;
-
-        let status = resp.status();
-        eval_status_code!(status, StatusCode::NO_CONTENT, None);
         Ok(())
     }
 
     async fn mute_recording(&self, recording_name: &str) -> Result<()> {
-        let resp = HTTP_CLIENT
-            .post(format!(
-                "{}/recordings/live/{}/mute",
-                self.url, recording_name
-            ))
-            .headers(self.get_common_headers()?)
-            .send()
+        let path = format!("/recordings/live/{}/mute", encode_segment(recording_name));
+        self.send_request(Method::POST, &path, None, StatusCode::NO_CONTENT)
             .await?;
-
-        let status = resp.status();
-        eval_status_code!(status, StatusCode::NO_CONTENT, None);
         Ok(())
     }
 
     async fn unmute_recording(&self, recording_name: &str) -> Result<()> {
-        let resp = HTTP_CLIENT
-            .delete(format!(
-                "{}/recordings/live/{}/mute",
-                self.url, recording_name
-            ))
-            .headers(self.get_common_headers()?)
-            .send()
+        let path = format!("/recordings/live/{}/mute", encode_segment(recording_name));
+        self.send_request(Method::DELETE, &path, None, StatusCode::NO_CONTENT)
             .await?;
-
-        let status = resp.status();
-        eval_status_code!(status, StatusCode::NO_CONTENT, None);
         Ok(())
     }
 
     async fn delete_recording(&self, recording_name: &str) -> Result<()> {
-        let resp = HTTP_CLIENT
-            .delete(format!("{}/recordings/live/{}", self.url, recording_name))
-            .headers(self.get_common_headers()?)
-            .send()
+        let path = format!("/recordings/live/{}", encode_segment(recording_name));
+        self.send_request(Method::DELETE, &path, None, StatusCode::NO_CONTENT)
             .await?;
-
-        let status = resp.status();
-        eval_status_code!(status, StatusCode::NO_CONTENT, None);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(apps: &[&str]) -> ConnectionOptions {
+        ConnectionOptions {
+            apps: apps.iter().map(|app| app.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    // cargo test -- --show-output test_websocket_url
+    #[test]
+    fn test_websocket_url() {
+        let url = websocket_url("http://localhost:8088/ari", &options(&["my-ast-app"])).unwrap();
+        assert_eq!(
+            url.as_str(),
+            "ws://localhost:8088/ari/events?app=my-ast-app"
+        );
+
+        // default port and reverse proxy path prefix
+        let url = websocket_url(
+            "https://pbx.example.com/asterisk/ari/",
+            &options(&["app1", "app2"]),
+        )
+        .unwrap();
+        assert_eq!(
+            url.as_str(),
+            "wss://pbx.example.com/asterisk/ari/events?app=app1%2Capp2"
+        );
+
+        let url = websocket_url(
+            "http://[::1]:8088/ari",
+            &ConnectionOptions {
+                subscribe_all: true,
+                ..options(&["app"])
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            url.as_str(),
+            "ws://[::1]:8088/ari/events?app=app&subscribeAll=true"
+        );
+
+        assert!(websocket_url("ftp://localhost/ari", &options(&["app"])).is_err());
+        assert!(websocket_url("localhost:8088/ari", &options(&["app"])).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+use super::AriClient;
+use crate::connection::{pem_certificates, KeepaliveConfig, ReconnectPolicy, TlsConfig};
+use crate::errors::{Error, Result};
+use crate::retry::RetryPolicy;
+use log::*;
+use native_tls::TlsConnector;
+use reqwest::header::HeaderMap;
+use reqwest::Proxy;
+use std::time::Duration;
+
+/// Builder of [AriClient] owning its own HTTP client, see [AriClient::builder].
+///
+/// ```no_run
+/// use asterisk_ari_client_rs::client::AriClient;
+/// use std::time::Duration;
+///
+/// # fn example() -> asterisk_ari_client_rs::errors::Result<()> {
+/// let client = AriClient::builder()
+///     .url("http://localhost:8088/ari")
+///     .credentials("asterisk", "asterisk")
+///     .timeout(Duration::from_secs(5))
+///     .user_agent("my-ivr/1.0")
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct AriClientBuilder {
+    url: Option<String>,
+    user: String,
+    password: String,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    pool_max_idle_per_host: Option<usize>,
+    pool_idle_timeout: Option<Duration>,
+    proxy: Option<Proxy>,
+    user_agent: Option<String>,
+    default_headers: Option<HeaderMap>,
+    tls_config: Option<TlsConfig>,
+    reconnect_policy: Option<ReconnectPolicy>,
+    keepalive: Option<KeepaliveConfig>,
+    retry_policy: Option<RetryPolicy>,
+}
+
+impl AriClientBuilder {
+    /// ARI base URL, e.g. http://localhost:8088/ari
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    /// ARI user and password (see ari.conf).
+    pub fn credentials(mut self, user: impl Into<String>, password: impl Into<String>) -> Self {
+        self.user = user.into();
+        self.password = password.into();
+        self
+    }
+
+    /// Total timeout of each REST call (connect, send request, read response).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Timeout of connect phase of REST calls.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Maximal number of idle connections kept in connection pool.
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// How long idle connections are kept in connection pool.
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Proxy used for REST calls.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Headers sent with every REST call.
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        self.default_headers = Some(headers);
+        self
+    }
+
+    /// TLS settings used for both REST calls and the websocket connection.
+    pub fn tls_config(mut self, tls_config: TlsConfig) -> Self {
+        self.tls_config = Some(tls_config);
+        self
+    }
+
+    /// See [AriClient::set_reconnect_policy].
+    pub fn reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = Some(policy);
+        self
+    }
+
+    /// See [AriClient::set_keepalive].
+    pub fn keepalive(mut self, keepalive: KeepaliveConfig) -> Self {
+        self.keepalive = Some(keepalive);
+        self
+    }
+
+    /// See [AriClient::set_retry_policy].
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    pub fn build(self) -> Result<AriClient> {
+        let url = match self.url {
+            Some(url) => url,
+            None => return Err(Error::Config("ARI url not specified".into())),
+        };
+
+        let mut http_client_builder = reqwest::Client::builder();
+        if let Some(timeout) = self.timeout {
+            http_client_builder = http_client_builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            http_client_builder = http_client_builder.connect_timeout(timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            http_client_builder = http_client_builder.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            http_client_builder = http_client_builder.pool_idle_timeout(timeout);
+        }
+        if let Some(proxy) = self.proxy {
+            http_client_builder = http_client_builder.proxy(proxy);
+        }
+        if let Some(user_agent) = self.user_agent {
+            http_client_builder = http_client_builder.user_agent(user_agent);
+        }
+        if let Some(headers) = self.default_headers {
+            http_client_builder = http_client_builder.default_headers(headers);
+        }
+
+        let mut tls_connector = None;
+        if let Some(tls_config) = &self.tls_config {
+            let mut connector_builder = TlsConnector::builder();
+
+            if let Some(ca_bundle_pem) = &tls_config.ca_bundle_pem {
+                for cert_pem in pem_certificates(ca_bundle_pem) {
+                    http_client_builder = http_client_builder
+                        .add_root_certificate(reqwest::Certificate::from_pem(cert_pem)?);
+                    connector_builder
+                        .add_root_certificate(native_tls::Certificate::from_pem(cert_pem)?);
+                }
+            }
+
+            if let Some(identity) = &tls_config.client_identity {
+                http_client_builder = http_client_builder.identity(
+                    reqwest::Identity::from_pkcs8_pem(&identity.cert_pem, &identity.key_pem)?,
+                );
+                connector_builder.identity(native_tls::Identity::from_pkcs8(
+                    &identity.cert_pem,
+                    &identity.key_pem,
+                )?);
+            }
+
+            if tls_config.insecure {
+                warn!("TLS certificate and hostname verification disabled");
+                http_client_builder = http_client_builder
+                    .danger_accept_invalid_certs(true)
+                    .danger_accept_invalid_hostnames(true);
+                connector_builder
+                    .danger_accept_invalid_certs(true)
+                    .danger_accept_invalid_hostnames(true);
+            }
+
+            tls_connector = Some(connector_builder.build()?);
+        }
+
+        let mut client = AriClient::with_http_client(
+            url,
+            self.user,
+            self.password,
+            http_client_builder.build()?,
+            tls_connector,
+        );
+        client.set_reconnect_policy(self.reconnect_policy);
+        client.set_retry_policy(self.retry_policy);
+        if let Some(keepalive) = self.keepalive {
+            client.set_keepalive(keepalive);
+        }
+        Ok(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // cargo test -- --show-output test_builder
+    #[test]
+    fn test_builder() {
+        assert!(AriClient::builder().build().is_err());
+
+        let client = AriClient::builder()
+            .url("http://localhost:8088/ari")
+            .credentials("asterisk", "secret")
+            .timeout(Duration::from_secs(5))
+            .connect_timeout(Duration::from_secs(1))
+            .pool_max_idle_per_host(4)
+            .user_agent("test-agent")
+            .tls_config(TlsConfig {
+                insecure: true,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        assert_eq!(client.url, "http://localhost:8088/ari");
+        assert_eq!(client.user, "asterisk");
+        assert_eq!(client.password, "secret");
+        assert!(client.tls_connector.is_some());
+        assert!(client.retry_policy.is_none());
+
+        let client = AriClient::builder()
+            .url("http://localhost:8088/ari")
+            .retry_policy(RetryPolicy::default())
+            .build()
+            .unwrap();
+        assert!(client.retry_policy.is_some());
+        assert!(client.with_retry_policy(None).retry_policy.is_none());
+    }
+}
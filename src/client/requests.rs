@@ -0,0 +1,259 @@
+//! Request bodies and query strings of ARI REST calls. Values are always serialized
+//! by serde (JSON bodies) or url encoded (path segments, query parameters), never interpolated as is.
+
+use crate::errors::{Error, Result};
+use crate::models::channels::Direction;
+use crate::models::recordings::{IfExists, RecordingFormat, TerminateOn};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// everything except unreserved characters (RFC 3986), i.e. also '/' is encoded
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// encodes single path segment (channel id, recording name, ...)
+pub(crate) fn encode_segment(segment: &str) -> String {
+    utf8_percent_encode(segment, PATH_SEGMENT).to_string()
+}
+
+/// appends url encoded query string to the path
+pub(crate) fn with_query<T: Serialize>(path: String, query: &T) -> Result<String> {
+    let query = serde_urlencoded::to_string(query).map_err(|err| Error::Config(err.to_string()))?;
+    if query.is_empty() {
+        Ok(path)
+    } else {
+        Ok(format!("{}?{}", path, query))
+    }
+}
+
+/// POST /channels/{channelId}/play
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PlayRequest<'a> {
+    pub media: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub playback_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lang: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offsetms: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipms: Option<usize>,
+}
+
+/// GET /channels/{channelId}/variable
+#[derive(Debug, Serialize)]
+pub(crate) struct GetVariableQuery<'a> {
+    pub variable: &'a str,
+}
+
+/// POST /channels/{channelId}/variable
+#[derive(Debug, Serialize)]
+pub(crate) struct SetVariableQuery<'a> {
+    pub variable: &'a str,
+    pub value: &'a str,
+}
+
+/// POST /channels/{channelId}/snoop
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SnoopRequest<'a> {
+    pub app: &'a str,
+    pub spy: &'a Direction,
+    pub whisper: &'a Direction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_args: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snoop_id: Option<&'a str>,
+}
+
+/// POST /channels/{channelId}/record
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RecordRequest<'a> {
+    pub name: &'a str,
+    pub format: &'a RecordingFormat,
+    pub terminate_on: &'a TerminateOn,
+    pub max_duration_seconds: usize,
+    pub max_silence_seconds: usize,
+    pub if_exists: &'a IfExists,
+    pub beep: bool,
+}
+
+/// POST /channels
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OriginateRequest<'a> {
+    pub endpoint: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_args: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caller_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub other_channel_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub originator: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub formats: Option<&'a str>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub variables: &'a HashMap<String, String>,
+}
+
+/// POST /channels/create
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CreateChannelRequest<'a> {
+    pub endpoint: &'a str,
+    pub app: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_args: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub other_channel_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub originator: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub formats: Option<&'a str>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub variables: &'a HashMap<String, String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // cargo test -- --show-output test_encode_segment
+    #[test]
+    fn test_encode_segment() {
+        assert_eq!(encode_segment("1607454635.2"), "1607454635.2");
+        assert_eq!(encode_segment("my rec/\"1\"?#"), "my%20rec%2F%221%22%3F%23");
+        assert_eq!(encode_segment("žluťoučký"), "%C5%BElu%C5%A5ou%C4%8Dk%C3%BD");
+    }
+
+    // cargo test -- --show-output test_with_query
+    #[test]
+    fn test_with_query() {
+        let path = with_query(
+            format!("/channels/{}/variable", encode_segment("chan 1")),
+            &SetVariableQuery {
+                variable: "CALLERID(name)",
+                value: "a&b=c \"quoted\" 100%",
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            path,
+            "/channels/chan%201/variable?variable=CALLERID%28name%29&value=a%26b%3Dc+%22quoted%22+100%25"
+        );
+    }
+
+    // cargo test -- --show-output test_request_bodies
+    #[test]
+    fn test_request_bodies() {
+        let body = serde_json::to_string(&PlayRequest {
+            media: "sound:/tmp/it's \"quoted\"\\path",
+            playback_id: None,
+            lang: Some("en"),
+            offsetms: None,
+            skipms: None,
+        })
+        .unwrap();
+        assert_eq!(
+            body,
+            r#"{"media":"sound:/tmp/it's \"quoted\"\\path","lang":"en"}"#
+        );
+        let value: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(value["media"], "sound:/tmp/it's \"quoted\"\\path");
+
+        let body = serde_json::to_string(&SnoopRequest {
+            app: "app\"}",
+            spy: &Direction::In,
+            whisper: &Direction::None,
+            app_args: None,
+            snoop_id: Some("snoop-1"),
+        })
+        .unwrap();
+        assert_eq!(
+            body,
+            r#"{"app":"app\"}","spy":"in","whisper":"none","snoopId":"snoop-1"}"#
+        );
+
+        let body = serde_json::to_string(&RecordRequest {
+            name: "rec\\\"1",
+            format: &RecordingFormat::Wav16,
+            terminate_on: &TerminateOn::Hash,
+            max_duration_seconds: 60,
+            max_silence_seconds: 0,
+            if_exists: &IfExists::Overwrite,
+            beep: true,
+        })
+        .unwrap();
+        assert_eq!(
+            body,
+            r##"{"name":"rec\\\"1","format":"wav16","terminateOn":"#","maxDurationSeconds":60,"maxSilenceSeconds":0,"ifExists":"overwrite","beep":true}"##
+        );
+    }
+
+    // cargo test -- --show-output test_originate_request
+    #[test]
+    fn test_originate_request() {
+        let mut variables = HashMap::new();
+        variables.insert("CALLERID(name)".to_string(), "Alice \"A\"".to_string());
+        let body = serde_json::to_string(&OriginateRequest {
+            endpoint: "PJSIP/1001",
+            extension: None,
+            context: None,
+            priority: None,
+            label: None,
+            app: Some("my-app"),
+            app_args: None,
+            caller_id: None,
+            timeout: Some(20),
+            channel_id: None,
+            other_channel_id: None,
+            originator: None,
+            formats: None,
+            variables: &variables,
+        })
+        .unwrap();
+        assert_eq!(
+            body,
+            r#"{"endpoint":"PJSIP/1001","app":"my-app","timeout":20,"variables":{"CALLERID(name)":"Alice \"A\""}}"#
+        );
+
+        let body = serde_json::to_string(&CreateChannelRequest {
+            endpoint: "PJSIP/1001",
+            app: "my-app",
+            app_args: None,
+            channel_id: Some("chan-1"),
+            other_channel_id: None,
+            originator: None,
+            formats: None,
+            variables: &HashMap::new(),
+        })
+        .unwrap();
+        assert_eq!(
+            body,
+            r#"{"endpoint":"PJSIP/1001","app":"my-app","channelId":"chan-1"}"#
+        );
+    }
+}
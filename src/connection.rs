@@ -0,0 +1,279 @@
+//! Websocket connection settings and state notifications
+//! used by [ari_processing_loop](crate::client::AriClient::ari_processing_loop).
+
+use rand::Rng;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// State of the ARI websocket connection, see
+/// [set_connection_state_sender](crate::client::AriClient::set_connection_state_sender).
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConnectionState {
+    /// Websocket connection was (re)established.
+    Connected,
+
+    /// Websocket connection was lost or closed by Asterisk.
+    Disconnected,
+
+    /// Reconnection attempt (counted from 1) will be made after given delay.
+    Reconnecting { attempt: u32, delay: Duration },
+}
+
+/// Options of the ARI websocket (/events) connection, see
+/// [ari_processing_loop_with_options](crate::client::AriClient::ari_processing_loop_with_options).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConnectionOptions {
+    /// Applications to subscribe.
+    pub apps: Vec<String>,
+
+    /// Subscribe to all Asterisk events (subscribeAll=true), not just the events
+    /// of resources the applications are subscribed to.
+    pub subscribe_all: bool,
+
+    /// How to authenticate websocket upgrade request.
+    pub auth: WebsocketAuth,
+}
+
+/// Authentication of the websocket upgrade request.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum WebsocketAuth {
+    /// Basic Authorization header, same as used by REST calls.
+    #[default]
+    Header,
+
+    /// api_key=user:password query parameter. Credentials end up in Asterisk and proxy access logs,
+    /// use only when the header cannot be passed through (e.g. by some reverse proxies).
+    ApiKey,
+}
+
+/// Exponential backoff applied when (re)connecting the websocket.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Delay before the first reconnection attempt.
+    pub initial_delay: Duration,
+
+    /// Upper bound of the delay between attempts.
+    pub max_delay: Duration,
+
+    /// Factor the delay is multiplied by after each failed attempt, values below 1.0 are treated as 1.0.
+    pub multiplier: f64,
+
+    /// Random jitter as a fraction of the delay, e.g. 0.2 means +/- 20%. Clamped to 0.0..=1.0,
+    /// NaN disables the jitter.
+    pub jitter: f64,
+
+    /// Maximal number of consecutive failed attempts. None means reconnecting forever.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before given reconnection attempt (counted from 1), jitter not applied.
+    pub fn base_delay(&self, attempt: u32) -> Duration {
+        exponential_delay(self.initial_delay, self.multiplier, self.max_delay, attempt)
+    }
+
+    /// Delay before given reconnection attempt (counted from 1) with random jitter applied.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let base_delay = self.base_delay(attempt);
+        let max_jitter = self.jitter.min(1.0);
+        if self.jitter.is_nan() || max_jitter <= 0.0 {
+            return base_delay;
+        }
+        let jitter = rand::thread_rng().gen_range(-max_jitter..=max_jitter);
+        base_delay.mul_f64((1.0 + jitter).max(0.0))
+    }
+
+    /// Whether another attempt is allowed after given number of failed attempts.
+    pub fn allows_attempt(&self, attempt: u32) -> bool {
+        match self.max_attempts {
+            Some(max_attempts) => attempt <= max_attempts,
+            None => true,
+        }
+    }
+}
+
+/// TLS settings for https/wss connections to Asterisk, see
+/// [tls_config](crate::client::AriClientBuilder::tls_config).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TlsConfig {
+    /// PEM encoded CA certificate(s) trusted in addition to system trust store.
+    pub ca_bundle_pem: Option<Vec<u8>>,
+
+    /// Client certificate used for mutual TLS authentication.
+    pub client_identity: Option<ClientIdentity>,
+
+    /// Disables certificate and hostname verification. Intended for lab PBXs with self-signed certificates only!
+    pub insecure: bool,
+}
+
+/// PEM encoded client certificate chain and its PKCS#8 private key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientIdentity {
+    pub cert_pem: Vec<u8>,
+
+    pub key_pem: Vec<u8>,
+}
+
+/// splits PEM bundle into individual certificates
+pub(crate) fn pem_certificates(bundle: &[u8]) -> Vec<&[u8]> {
+    const END_MARKER: &[u8] = b"-----END CERTIFICATE-----";
+
+    let mut certificates = vec![];
+    let mut rest = bundle;
+    while let Some(end) = rest
+        .windows(END_MARKER.len())
+        .position(|window| window == END_MARKER)
+    {
+        let (certificate, tail) = rest.split_at(end + END_MARKER.len());
+        certificates.push(certificate);
+        rest = tail;
+    }
+    certificates
+}
+
+/// Websocket keepalive settings. Ping is sent every ping_interval, when no pong (or any other message)
+/// arrives within pong_timeout the connection is considered dead: ari_processing_loop either
+/// reconnects (when reconnect policy is set) or returns [KeepaliveTimeout](crate::errors::Error::KeepaliveTimeout) error.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeepaliveConfig {
+    /// Zero disables keepalive, i.e. no pings are sent and dead connection is not detected.
+    pub ping_interval: Duration,
+
+    /// None disables dead connection detection.
+    pub pong_timeout: Option<Duration>,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        KeepaliveConfig {
+            ping_interval: Duration::from_millis(5000),
+            pong_timeout: Some(Duration::from_millis(10000)),
+        }
+    }
+}
+
+/// initial * multiplier^(attempt - 1) capped by max, multiplier below 1.0 (or NaN) is treated as 1.0
+pub(crate) fn exponential_delay(
+    initial: Duration,
+    multiplier: f64,
+    max: Duration,
+    attempt: u32,
+) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+    let delay_secs = initial.as_secs_f64() * multiplier.max(1.0).powi(exponent);
+    if !delay_secs.is_finite() || delay_secs >= max.as_secs_f64() {
+        max
+    } else {
+        Duration::from_secs_f64(delay_secs)
+    }
+}
+
+/// Handle used to stop [ari_processing_loop](crate::client::AriClient::ari_processing_loop).
+/// Shared by the client and all its clones, see [shutdown_handle](crate::client::AriClient::shutdown_handle).
+#[derive(Clone, Debug)]
+pub struct ShutdownHandle {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Default for ShutdownHandle {
+    fn default() -> Self {
+        ShutdownHandle {
+            sender: Arc::new(watch::channel(false).0),
+        }
+    }
+}
+
+impl ShutdownHandle {
+    /// Request shutdown: websocket is closed with Close frame, events received until
+    /// Asterisk acknowledges the close are still delivered and then the loop returns Ok(())
+    /// once queued events are handed over to typed senders and event sinks (see [EventSink::flush](crate::sink::EventSink::flush)).
+    /// No reconnection is attempted after the shutdown.
+    pub fn shutdown(&self) {
+        let _ = self.sender.send(true);
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    pub(crate) fn subscribe(&self) -> watch::Receiver<bool> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // cargo test -- --show-output test_pem_certificates
+    #[test]
+    fn test_pem_certificates() {
+        let bundle = b"# root CA\n-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n-----BEGIN CERTIFICATE-----\nBBBB\n-----END CERTIFICATE-----\n";
+        let certificates = pem_certificates(bundle);
+        assert_eq!(certificates.len(), 2);
+        assert!(certificates[0].ends_with(b"AAAA\n-----END CERTIFICATE-----"));
+        assert!(certificates[1].starts_with(b"\n-----BEGIN CERTIFICATE-----\nBBBB"));
+        assert!(pem_certificates(b"").is_empty());
+    }
+
+    // cargo test -- --show-output test_reconnect_policy_backoff
+    #[test]
+    fn test_reconnect_policy_backoff() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            multiplier: 2.0,
+            jitter: 0.0,
+            max_attempts: Some(10),
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(4), Duration::from_millis(800));
+        assert_eq!(policy.delay(5), Duration::from_millis(1000));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_millis(1000));
+        assert!(policy.allows_attempt(10));
+        assert!(!policy.allows_attempt(11));
+    }
+
+    // cargo test -- --show-output test_reconnect_policy_jitter
+    #[test]
+    fn test_reconnect_policy_jitter() {
+        let policy = ReconnectPolicy::default();
+        for _ in 0..100 {
+            let delay = policy.delay(1);
+            assert!(delay >= Duration::from_millis(400) && delay <= Duration::from_millis(600));
+        }
+    }
+
+    // cargo test -- --show-output test_reconnect_policy_invalid
+    #[test]
+    fn test_reconnect_policy_invalid() {
+        let mut policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            multiplier: -2.0,
+            jitter: f64::NAN,
+            max_attempts: None,
+        };
+        assert_eq!(policy.delay(2), Duration::from_millis(100));
+        policy.multiplier = f64::NAN;
+        assert_eq!(policy.delay(3), Duration::from_millis(100));
+        policy.jitter = 5.0;
+        for _ in 0..100 {
+            assert!(policy.delay(1) <= Duration::from_millis(200));
+        }
+    }
+}
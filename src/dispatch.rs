@@ -0,0 +1,293 @@
+//! Event dispatch sharded by channel (or bridge): events with the same key are handled strictly in order
+//! one by one, events with different keys in parallel. Every key has its own queue and task
+//! (spawned on demand, finished once the queue is drained). By default every queue holds up to 100 events and
+//! a full queue drops its oldest event ([DropOldest](BackpressurePolicy::DropOldest)), i.e. slow handling of one call
+//! never delays the others nor grows memory without limit; dropped events are counted in [stats](KeyedDispatcher::stats).
+//! With [Block](BackpressurePolicy::Block) no event is lost but a full queue of a single key suspends websocket
+//! processing for all keys:
+//!
+//! ```no_run
+//! use asterisk_ari_client_rs::client::AriClient;
+//! use asterisk_ari_client_rs::dispatch::KeyedDispatcher;
+//! use asterisk_ari_client_rs::models::events::AriEvent;
+//! use asterisk_ari_client_rs::sink::BackpressurePolicy;
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> asterisk_ari_client_rs::errors::Result<()> {
+//! let client = AriClient::new("http://localhost:8088/ari".into(), "asterisk".into(), "asterisk".into());
+//! let dispatcher = KeyedDispatcher::new(|event| async move {
+//!     if let AriEvent::ChannelDtmfReceived(event) = event {
+//!         // digits of the same channel are never reordered
+//!         println!("call {} pressed {}", event.channel.id, event.digit);
+//!     }
+//! })
+//! .buffer(32);
+//! client.add_event_sink(Arc::new(dispatcher));
+//! client.ari_processing_loop(vec!["my-ast-app".into()]).await
+//! # }
+//! ```
+
+use crate::models::events::AriEvent;
+use crate::sink::{event_queue, BackpressurePolicy, DeliveryStats, EventQueueSender, EventSink};
+use async_trait::async_trait;
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use log::*;
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+type KeyedHandler = dyn Fn(AriEvent) -> BoxFuture<'static, ()> + Send + Sync;
+
+/// Function deriving dispatch key from the event, events without key share single queue.
+pub type KeyFn = fn(&AriEvent) -> Option<String>;
+
+/// Default dispatch key: `channel:<id>` for events related to a channel (including playbacks
+/// and recordings targeting the channel), `bridge:<id>` for bridge events not carrying channel.
+pub fn default_key(event: &AriEvent) -> Option<String> {
+    if let Some(channel_id) = event.channel_id() {
+        return Some(format!("channel:{}", channel_id));
+    }
+    event.bridge().map(|bridge| format!("bridge:{}", bridge.id))
+}
+
+/// [EventSink] invoking handler in per-key tasks, see [module documentation](self).
+pub struct KeyedDispatcher {
+    handler: Arc<KeyedHandler>,
+    key: KeyFn,
+    buffer: usize,
+    policy: BackpressurePolicy,
+    workers: Arc<Mutex<HashMap<String, Arc<EventQueueSender<AriEvent>>>>>,
+    // notified whenever a worker finishes
+    worker_finished: Arc<Notify>,
+    stats: Arc<Mutex<DeliveryStats>>,
+}
+
+impl KeyedDispatcher {
+    /// Dispatcher keyed by [default_key] with queue of 100 events per key dropping the oldest event when full.
+    pub fn new<F, Fut>(handler: F) -> Self
+    where
+        F: Fn(AriEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        KeyedDispatcher {
+            handler: Arc::new(move |event| Box::pin(handler(event))),
+            key: default_key,
+            buffer: 100,
+            policy: BackpressurePolicy::DropOldest,
+            workers: Arc::new(Mutex::new(HashMap::new())),
+            worker_finished: Arc::new(Notify::new()),
+            stats: Arc::new(Mutex::new(DeliveryStats::default())),
+        }
+    }
+
+    /// Custom dispatch key, e.g. `|_| None` to handle all events sequentially.
+    pub fn key(mut self, key: KeyFn) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Maximal number of events buffered per key (100 by default), ignored by [Unbounded](BackpressurePolicy::Unbounded) policy.
+    pub fn buffer(mut self, buffer: usize) -> Self {
+        self.buffer = buffer;
+        self
+    }
+
+    /// What to do when buffer of a key is full, [DropOldest](BackpressurePolicy::DropOldest) by default.
+    /// Note that [Block](BackpressurePolicy::Block) suspends websocket processing, i.e. delivery to all keys,
+    /// until the handler of the key catches up.
+    pub fn policy(mut self, policy: BackpressurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Number of keys with events being handled.
+    pub fn active_keys(&self) -> usize {
+        self.workers.lock().unwrap().len()
+    }
+
+    /// Delivery metrics of all keys combined (queued events are counted for active keys only).
+    pub fn stats(&self) -> DeliveryStats {
+        let workers = self.workers.lock().unwrap();
+        let stats = *self.stats.lock().unwrap();
+        workers
+            .values()
+            .fold(stats, |total, queue| total + queue.stats())
+    }
+
+    /// queue of the key, new worker is spawned if there is none
+    fn queue(&self, key: &str) -> Arc<EventQueueSender<AriEvent>> {
+        let mut workers = self.workers.lock().unwrap();
+        if let Some(queue) = workers.get(key) {
+            return queue.clone();
+        }
+
+        let (queue, mut receiver) = event_queue(self.buffer, self.policy);
+        let queue = Arc::new(queue);
+        workers.insert(key.to_owned(), queue.clone());
+
+        let key = key.to_owned();
+        let handler = self.handler.clone();
+        let workers = self.workers.clone();
+        let worker_finished = self.worker_finished.clone();
+        let stats = self.stats.clone();
+        tokio::spawn(async move {
+            let mut next = receiver.recv().await;
+            while let Some(event) = next {
+                let event_type = event.event_type();
+                if AssertUnwindSafe(handler(event))
+                    .catch_unwind()
+                    .await
+                    .is_err()
+                {
+                    error!(
+                        "event handler panicked when handling {} ({})",
+                        event_type, key
+                    );
+                }
+
+                next = receiver.try_recv();
+                if next.is_none() {
+                    // queue drained, deregister unless new event has arrived meanwhile
+                    let mut workers = workers.lock().unwrap();
+                    if receiver.close_if_empty() {
+                        if let Some(queue) = workers.remove(&key) {
+                            let mut stats = stats.lock().unwrap();
+                            *stats = *stats + queue.stats();
+                        }
+                        worker_finished.notify_waiters();
+                    } else {
+                        next = receiver.try_recv();
+                    }
+                }
+            }
+        });
+        queue
+    }
+}
+
+#[async_trait]
+impl EventSink for KeyedDispatcher {
+    async fn deliver(&self, event: &AriEvent) {
+        let key = (self.key)(event).unwrap_or_default();
+        // worker might have just finished, i.e. its queue is closed and new one is needed
+        while !self.queue(&key).push(event.clone()).await {}
+    }
+
+    /// Waits until events of all keys are handled.
+    async fn flush(&self) {
+        loop {
+            // registered before the check, i.e. worker finishing in between is not missed
+            let finished = self.worker_finished.notified();
+            if self.active_keys() == 0 {
+                return;
+            }
+            finished.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::AriClient;
+    use crate::testing::fixtures::dtmf;
+    use tokio::sync::mpsc;
+    use tokio::time::{sleep, timeout, Duration};
+
+    // cargo test -- --show-output test_keyed_dispatcher
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_keyed_dispatcher() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let dispatcher = Arc::new(
+            KeyedDispatcher::new(move |event| {
+                let sender = sender.clone();
+                async move {
+                    if let AriEvent::ChannelDtmfReceived(event) = event {
+                        // random delays would reorder digits if handled in parallel
+                        sleep(Duration::from_millis(rand::random::<u64>() % 3)).await;
+                        let _ = sender.send((event.channel.id, event.digit));
+                    }
+                }
+            })
+            .policy(BackpressurePolicy::Block)
+            .buffer(4),
+        );
+        let client = AriClient::new("http://localhost:8088/ari".into(), "".into(), "".into());
+        client.add_event_sink(dispatcher.clone());
+
+        let channels = ["a", "b", "c"];
+        for round in 0..30 {
+            for channel_id in channels {
+                client
+                    .dispatch_event(dtmf(channel_id, &round.to_string()))
+                    .await;
+            }
+        }
+
+        let mut digits: HashMap<String, Vec<String>> = HashMap::new();
+        for _ in 0..90 {
+            let (channel_id, digit) = timeout(Duration::from_secs(5), receiver.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            digits.entry(channel_id).or_default().push(digit);
+        }
+        let expected: Vec<String> = (0..30).map(|round| round.to_string()).collect();
+        for channel_id in channels {
+            assert_eq!(digits[channel_id], expected);
+        }
+
+        // workers are finished once there is nothing to handle
+        timeout(Duration::from_secs(5), async {
+            while dispatcher.active_keys() > 0 {
+                sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let stats = dispatcher.stats();
+        assert_eq!(stats.delivered, 90);
+        assert_eq!(stats.dropped, 0);
+        assert!(stats.max_queued <= 4);
+    }
+
+    // cargo test -- --show-output test_keyed_dispatcher_default_bounded
+    #[tokio::test]
+    async fn test_keyed_dispatcher_default_bounded() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let dispatcher = Arc::new(KeyedDispatcher::new(move |event| {
+            let sender = sender.clone();
+            async move {
+                if let AriEvent::ChannelDtmfReceived(event) = event {
+                    let _ = sender.send((event.channel.id, event.digit));
+                }
+            }
+        }));
+        let client = AriClient::new("http://localhost:8088/ari".into(), "".into(), "".into());
+        client.add_event_sink(dispatcher.clone());
+
+        // worker of "a" cannot run before the burst is over, full queue must not stall "b"
+        for digit in 0..150 {
+            client.dispatch_event(dtmf("a", &digit.to_string())).await;
+        }
+        client.dispatch_event(dtmf("b", "1")).await;
+        timeout(Duration::from_secs(5), dispatcher.flush())
+            .await
+            .unwrap();
+
+        let mut digits: HashMap<String, Vec<String>> = HashMap::new();
+        while let Ok((channel_id, digit)) = receiver.try_recv() {
+            digits.entry(channel_id).or_default().push(digit);
+        }
+        // the oldest events were dropped
+        let expected: Vec<String> = (50..150).map(|digit| digit.to_string()).collect();
+        assert_eq!(digits["a"], expected);
+        assert_eq!(digits["b"], vec!["1".to_string()]);
+        let stats = dispatcher.stats();
+        assert_eq!(stats.delivered, 151);
+        assert_eq!(stats.dropped, 50);
+    }
+}
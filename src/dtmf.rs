@@ -0,0 +1,302 @@
+//! DTMF digit collection built on top of [ChannelDtmfReceived](crate::models::events::ChannelDtmfReceived) events.
+//! [AriClient::collect_dtmf] takes events from own lossless subscription
+//! (see [subscribe_events_with_policy](crate::client::AriClient::subscribe_events_with_policy)),
+//! i.e. [ari_processing_loop](crate::client::AriClient::ari_processing_loop) must be running on the same client
+//! (or any of its clones) for the collection to work.
+//! Collection works with any [Ari] implementation, see [collect_dtmf].
+
+use crate::apis::Ari;
+use crate::client::AriClient;
+use crate::errors::{Error, Result};
+use crate::models::events::AriEvent;
+use crate::sink::{BackpressurePolicy, EventQueueReceiver};
+use async_trait::async_trait;
+use log::*;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::{sleep_until, Duration, Instant};
+
+/// Options controlling how DTMF digits are gathered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DtmfOptions {
+    /// Maximum number of digits to collect. None means no limit (terminator or timeout must end the collection).
+    pub max_digits: Option<usize>,
+
+    /// Digits terminating the collection (e.g. "#"). Terminator is not included in collected digits.
+    pub terminators: String,
+
+    /// How long to wait for the first digit. Starts once the prompt (if any) has finished playing
+    /// (or [prompt_timeout](Self::prompt_timeout) expired).
+    pub first_digit_timeout: Duration,
+
+    /// How long to wait for each subsequent digit.
+    pub inter_digit_timeout: Duration,
+
+    /// Media URI (e.g. sound:enter-account-number) played to the channel before collecting digits.
+    pub prompt: Option<String>,
+
+    /// Longest expected duration of the prompt. When PlaybackFinished of the prompt is not received within
+    /// this time (e.g. playback failed or the event was lost) the prompt is considered finished.
+    pub prompt_timeout: Duration,
+
+    /// When true first digit stops the prompt playback and is collected.
+    /// When false digits received while the prompt is playing are ignored.
+    pub barge_in: bool,
+}
+
+impl Default for DtmfOptions {
+    fn default() -> Self {
+        DtmfOptions {
+            max_digits: None,
+            terminators: "#".into(),
+            first_digit_timeout: Duration::from_secs(5),
+            inter_digit_timeout: Duration::from_secs(3),
+            prompt: None,
+            prompt_timeout: Duration::from_secs(60),
+            barge_in: true,
+        }
+    }
+}
+
+/// Outcome of DTMF collection. Every variant carries digits collected so far.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DtmfResult {
+    /// Maximum number of digits was collected.
+    Complete(String),
+
+    /// One of the terminator digits was received.
+    Terminated { digits: String, terminator: char },
+
+    /// First digit or inter digit timeout expired.
+    Timeout(String),
+
+    /// Channel was hung up or left Stasis application.
+    Hangup(String),
+}
+
+/// Digit accumulator deciding when the collection is finished.
+#[derive(Debug)]
+struct DtmfCollector<'a> {
+    options: &'a DtmfOptions,
+    digits: String,
+}
+
+impl<'a> DtmfCollector<'a> {
+    fn new(options: &'a DtmfOptions) -> Self {
+        DtmfCollector {
+            options,
+            digits: String::new(),
+        }
+    }
+
+    /// Adds received digit, returns Some(result) once the collection is finished.
+    fn push(&mut self, digit: &str) -> Option<DtmfResult> {
+        for ch in digit.chars() {
+            if self.options.terminators.contains(ch) {
+                return Some(DtmfResult::Terminated {
+                    digits: std::mem::take(&mut self.digits),
+                    terminator: ch,
+                });
+            }
+
+            self.digits.push(ch);
+
+            if let Some(max_digits) = self.options.max_digits {
+                if self.digits.chars().count() >= max_digits {
+                    return Some(DtmfResult::Complete(std::mem::take(&mut self.digits)));
+                }
+            }
+        }
+        None
+    }
+
+    fn into_digits(self) -> String {
+        self.digits
+    }
+}
+
+/// Source of events the collection waits for.
+#[async_trait]
+pub(crate) trait DtmfEvents: Send {
+    /// Next event, None once no more events can arrive.
+    async fn next_event(&mut self) -> Result<Option<AriEvent>>;
+}
+
+#[async_trait]
+impl DtmfEvents for broadcast::Receiver<AriEvent> {
+    async fn next_event(&mut self) -> Result<Option<AriEvent>> {
+        match self.recv().await {
+            Ok(event) => Ok(Some(event)),
+            // digits, terminator or end of the prompt might be lost, i.e. result would be wrong
+            Err(RecvError::Lagged(skipped)) => Err(Error::EventsLagged(skipped)),
+            Err(RecvError::Closed) => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl DtmfEvents for EventQueueReceiver<AriEvent> {
+    async fn next_event(&mut self) -> Result<Option<AriEvent>> {
+        Ok(self.recv().await)
+    }
+}
+
+#[async_trait]
+impl DtmfEvents for mpsc::UnboundedReceiver<AriEvent> {
+    async fn next_event(&mut self) -> Result<Option<AriEvent>> {
+        Ok(self.recv().await)
+    }
+}
+
+impl AriClient {
+    /// Gather DTMF digits from the channel, optionally playing a prompt first.
+    /// Events are received via own unbounded queue, i.e. no digit can be lost.
+    pub async fn collect_dtmf(&self, channel_id: &str, options: DtmfOptions) -> Result<DtmfResult> {
+        // subscribe before starting the prompt so that no event can slip through
+        let mut events = self.subscribe_events_with_policy(1024, BackpressurePolicy::default());
+        collect_dtmf_from(self, &mut events, channel_id, options).await
+    }
+}
+
+/// Gather DTMF digits from the channel, optionally playing a prompt first.
+/// Events are taken from [subscribe_events](Ari::subscribe_events) bus, when the subscription lags behind
+/// (i.e. events might have been lost) [EventsLagged](Error::EventsLagged) error is returned.
+pub async fn collect_dtmf<A: Ari + ?Sized>(
+    ari: &A,
+    channel_id: &str,
+    options: DtmfOptions,
+) -> Result<DtmfResult> {
+    // subscribe before starting the prompt so that no event can slip through
+    let mut events = ari.subscribe_events();
+    collect_dtmf_from(ari, &mut events, channel_id, options).await
+}
+
+/// Gather DTMF digits from the channel taking events from given source (subscribed before the call).
+pub(crate) async fn collect_dtmf_from<A: Ari + ?Sized, E: DtmfEvents + ?Sized>(
+    ari: &A,
+    events: &mut E,
+    channel_id: &str,
+    options: DtmfOptions,
+) -> Result<DtmfResult> {
+    let mut playback_id = None;
+    if let Some(media) = &options.prompt {
+        let playback = ari.play(channel_id, media, None, None, None, None).await?;
+        playback_id = Some(playback.id);
+    }
+
+    let mut collector = DtmfCollector::new(&options);
+
+    // first digit timer is armed only once the prompt is finished, until then the deadline guards the prompt
+    let mut deadline = if playback_id.is_none() {
+        Instant::now() + options.first_digit_timeout
+    } else {
+        Instant::now() + options.prompt_timeout
+    };
+
+    loop {
+        let event = tokio::select! {
+            event = events.next_event() => event?,
+            _ = sleep_until(deadline) => {
+                if let Some(prompt_playback_id) = playback_id.take() {
+                    debug!("collect_dtmf: prompt playback {} not finished in time", prompt_playback_id);
+                    deadline = Instant::now() + options.first_digit_timeout;
+                    continue;
+                }
+                return Ok(DtmfResult::Timeout(collector.into_digits()));
+            }
+        };
+
+        match event {
+            Some(AriEvent::ChannelDtmfReceived(event)) if event.channel.id == channel_id => {
+                if let Some(prompt_playback_id) = &playback_id {
+                    if !options.barge_in {
+                        trace!("collect_dtmf: ignoring digit {} during prompt", event.digit);
+                        continue;
+                    }
+                    // playback might have just finished, i.e. error can be ignored
+                    if let Err(err) = ari.stop_play(prompt_playback_id).await {
+                        debug!("collect_dtmf: unable to stop prompt playback {:?}", err);
+                    }
+                    playback_id = None;
+                }
+
+                if let Some(result) = collector.push(&event.digit) {
+                    return Ok(result);
+                }
+                deadline = Instant::now() + options.inter_digit_timeout;
+            }
+            Some(AriEvent::PlaybackFinished(event))
+                if Some(&event.playback.id) == playback_id.as_ref() =>
+            {
+                playback_id = None;
+                deadline = Instant::now() + options.first_digit_timeout;
+            }
+            Some(AriEvent::ChannelHangupRequest(event)) if event.channel.id == channel_id => {
+                return Ok(DtmfResult::Hangup(collector.into_digits()));
+            }
+            Some(AriEvent::StasisEnd(event)) if event.channel.id == channel_id => {
+                return Ok(DtmfResult::Hangup(collector.into_digits()));
+            }
+            Some(AriEvent::ChannelDestroyed(event)) if event.channel.id == channel_id => {
+                return Ok(DtmfResult::Hangup(collector.into_digits()));
+            }
+            Some(_) => {}
+            // event source is gone, no more events of the channel can arrive
+            None => {
+                return Ok(DtmfResult::Hangup(collector.into_digits()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // cargo test -- --show-output test_dtmf_collector_max_digits
+    #[test]
+    fn test_dtmf_collector_max_digits() {
+        let options = DtmfOptions {
+            max_digits: Some(3),
+            ..Default::default()
+        };
+        let mut collector = DtmfCollector::new(&options);
+        assert_eq!(collector.push("1"), None);
+        assert_eq!(collector.push("2"), None);
+        assert_eq!(
+            collector.push("3"),
+            Some(DtmfResult::Complete("123".into()))
+        );
+    }
+
+    // cargo test -- --show-output test_dtmf_collector_terminator
+    #[test]
+    fn test_dtmf_collector_terminator() {
+        let options = DtmfOptions {
+            max_digits: Some(5),
+            terminators: "#*".into(),
+            ..Default::default()
+        };
+        let mut collector = DtmfCollector::new(&options);
+        assert_eq!(collector.push("4"), None);
+        assert_eq!(collector.push("2"), None);
+        assert_eq!(
+            collector.push("*"),
+            Some(DtmfResult::Terminated {
+                digits: "42".into(),
+                terminator: '*'
+            })
+        );
+    }
+
+    // cargo test -- --show-output test_dtmf_collector_no_limit
+    #[test]
+    fn test_dtmf_collector_no_limit() {
+        let options = DtmfOptions::default();
+        let mut collector = DtmfCollector::new(&options);
+        for digit in ["1", "2", "3", "4", "5", "6", "7"] {
+            assert_eq!(collector.push(digit), None);
+        }
+        assert_eq!(collector.into_digits(), "1234567");
+    }
+}
@@ -1,7 +1,10 @@
 use reqwest::header::InvalidHeaderValue;
 use reqwest::Error as ReqwError;
-use reqwest::StatusCode;
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+use std::fmt;
 use std::result;
+use std::time::Duration;
 use tokio_tungstenite::tungstenite::Error as WSError;
 use url::ParseError;
 
@@ -13,12 +16,54 @@ pub enum Error {
     HttpInvalidHeader(InvalidHeaderValue),
     Http(ReqwError),
     UrlParse(ParseError),
-    Websocket(WSError),
+    /// Boxed, tungstenite error is large and would bloat every Result of the crate (clippy::result_large_err).
+    Websocket(Box<WSError>),
+    Tls(native_tls::Error),
+    /// Reading or writing of event recording file failed.
+    Io(std::io::Error),
+    /// Subscriber of the event bus lagged behind, carries number of missed events.
+    EventsLagged(u64),
+    /// No pong (or any other message) received from Asterisk, carries time elapsed since the last message.
+    KeepaliveTimeout(Duration),
+    /// Invalid client configuration or request parameters, detected before anything was sent to Asterisk.
+    Config(String),
+    /// Operation is not supported by this client, carries name of the operation.
+    Unsupported(&'static str),
 }
 
 impl Error {
     pub fn new(code: StatusCode, content: Option<String>) -> Self {
-        Error::Api(ApiError { code, content })
+        Error::Api(ApiError {
+            code,
+            content,
+            message: None,
+            method: None,
+            path: None,
+        })
+    }
+
+    /// Error returned by ARI for given request. Asterisk JSON body ({"message": "..."}) is parsed into message.
+    pub fn api(method: Method, path: &str, code: StatusCode, content: Option<String>) -> Self {
+        let message = content.as_deref().and_then(|content| {
+            serde_json::from_str::<ApiErrorBody>(content)
+                .ok()
+                .map(|body| body.message)
+        });
+        Error::Api(ApiError {
+            code,
+            content,
+            message,
+            method: Some(method),
+            path: Some(path.to_owned()),
+        })
+    }
+
+    /// Classification of ARI error, None for non-API errors (transport, serialization, ...).
+    pub fn api_error_kind(&self) -> Option<ApiErrorKind> {
+        match self {
+            Error::Api(api_error) => Some(api_error.kind()),
+            _ => None,
+        }
     }
 }
 
@@ -27,7 +72,120 @@ pub type Result<T> = result::Result<T, Error>;
 #[derive(Debug)]
 pub struct ApiError {
     pub code: StatusCode,
+    /// Raw response body.
     pub content: Option<String>,
+    /// Error message parsed from Asterisk JSON response body.
+    pub message: Option<String>,
+    /// HTTP method of failed request.
+    pub method: Option<Method>,
+    /// Resource path (relative to ARI base URL) of failed request.
+    pub path: Option<String>,
+}
+
+impl ApiError {
+    pub fn kind(&self) -> ApiErrorKind {
+        match self.code {
+            StatusCode::BAD_REQUEST => ApiErrorKind::BadRequest,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ApiErrorKind::Unauthorized,
+            StatusCode::NOT_FOUND => ApiErrorKind::NotFound,
+            StatusCode::CONFLICT => {
+                // e.g. "Channel not in Stasis application"
+                let not_in_stasis = matches!(
+                    self.message.as_deref(),
+                    Some(message) if message.to_lowercase().contains("not in stasis")
+                );
+                if not_in_stasis {
+                    ApiErrorKind::NotInStasis
+                } else {
+                    ApiErrorKind::Conflict
+                }
+            }
+            code if code.is_server_error() => ApiErrorKind::ServerError,
+            _ => ApiErrorKind::Other,
+        }
+    }
+}
+
+/// Classification of errors returned by ARI.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiErrorKind {
+    /// 400, invalid parameters.
+    BadRequest,
+    /// 401 or 403, invalid credentials or insufficient permissions.
+    Unauthorized,
+    /// 404, resource (channel, playback, recording, ...) not found.
+    NotFound,
+    /// 409, channel is not in Stasis application.
+    NotInStasis,
+    /// 409, resource in conflicting state (e.g. recording with same name already exists).
+    Conflict,
+    /// 5xx
+    ServerError,
+    Other,
+}
+
+/// error body returned by Asterisk
+#[derive(Deserialize)]
+struct ApiErrorBody {
+    message: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ARI error {}", self.code)?;
+        if let (Some(method), Some(path)) = (&self.method, &self.path) {
+            write!(f, " ({} {})", method, path)?;
+        }
+        match (&self.message, &self.content) {
+            (Some(message), _) => write!(f, ": {}", message),
+            (None, Some(content)) => write!(f, ": {}", content),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Serde(e) => write!(f, "serialization error: {}", e),
+            Error::Utf8(e) => write!(f, "utf8 error: {}", e),
+            Error::Api(e) => write!(f, "{}", e),
+            Error::HttpInvalidHeader(e) => write!(f, "invalid http header: {}", e),
+            Error::Http(e) => write!(f, "http error: {}", e),
+            Error::UrlParse(e) => write!(f, "url parse error: {}", e),
+            Error::Websocket(e) => write!(f, "websocket error: {}", e),
+            Error::Tls(e) => write!(f, "tls error: {}", e),
+            Error::Io(e) => write!(f, "io error: {}", e),
+            Error::EventsLagged(skipped) => write!(f, "{} events were missed", skipped),
+            Error::KeepaliveTimeout(elapsed) => write!(
+                f,
+                "keepalive timeout, last message received {:?} ago",
+                elapsed
+            ),
+            Error::Config(message) => write!(f, "configuration error: {}", message),
+            Error::Unsupported(operation) => write!(f, "unsupported operation: {}", operation),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Serde(e) => Some(e),
+            Error::Utf8(e) => Some(e),
+            Error::HttpInvalidHeader(e) => Some(e),
+            Error::Http(e) => Some(e),
+            Error::UrlParse(e) => Some(e),
+            Error::Websocket(e) => Some(e.as_ref()),
+            Error::Tls(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Api(_)
+            | Error::EventsLagged(_)
+            | Error::KeepaliveTimeout(_)
+            | Error::Config(_)
+            | Error::Unsupported(_) => None,
+        }
+    }
 }
 
 impl From<serde_json::Error> for Error {
@@ -50,7 +208,13 @@ impl From<ParseError> for Error {
 
 impl From<WSError> for Error {
     fn from(e: WSError) -> Self {
-        Error::Websocket(e)
+        Error::Websocket(Box::new(e))
+    }
+}
+
+impl From<native_tls::Error> for Error {
+    fn from(e: native_tls::Error) -> Self {
+        Error::Tls(e)
     }
 }
 
@@ -65,3 +229,54 @@ impl From<ReqwError> for Error {
         Error::Http(e)
     }
 }
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // cargo test -- --show-output test_api_error_kind
+    #[test]
+    fn test_api_error_kind() {
+        let err = Error::api(
+            Method::POST,
+            "/channels/1607454635.2/answer",
+            StatusCode::CONFLICT,
+            Some("{\"message\": \"Channel not in Stasis application\"}".into()),
+        );
+        assert_eq!(err.api_error_kind(), Some(ApiErrorKind::NotInStasis));
+        assert_eq!(
+            err.to_string(),
+            "ARI error 409 Conflict (POST /channels/1607454635.2/answer): Channel not in Stasis application"
+        );
+
+        let err = Error::api(
+            Method::DELETE,
+            "/channels/unknown",
+            StatusCode::NOT_FOUND,
+            Some("{\"message\": \"Channel not found\"}".into()),
+        );
+        assert_eq!(err.api_error_kind(), Some(ApiErrorKind::NotFound));
+
+        let err = Error::api(Method::GET, "/channels", StatusCode::BAD_GATEWAY, None);
+        assert_eq!(err.api_error_kind(), Some(ApiErrorKind::ServerError));
+        assert_eq!(err.to_string(), "ARI error 502 Bad Gateway (GET /channels)");
+
+        let err = Error::KeepaliveTimeout(Duration::from_secs(1));
+        assert_eq!(err.api_error_kind(), None);
+
+        // client side failures are not attributed to Asterisk
+        let err = crate::client::AriClient::builder().build().err().unwrap();
+        assert!(matches!(err, Error::Config(_)));
+        assert_eq!(err.api_error_kind(), None);
+        assert_eq!(
+            err.to_string(),
+            "configuration error: ARI url not specified"
+        );
+    }
+}
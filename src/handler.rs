@@ -0,0 +1,271 @@
+//! Callback style alternative to typed senders (set_*_sender methods of [AriClient]).
+//! Application implements [AriEventHandler] overriding only methods for events it is interested in
+//! and registers it via [add_event_handler](AriClient::add_event_handler):
+//!
+//! ```no_run
+//! use asterisk_ari_client_rs::client::AriClient;
+//! use asterisk_ari_client_rs::handler::{AriEventHandler, HandlerConcurrency};
+//! use asterisk_ari_client_rs::models::events::{ChannelDtmfReceived, StasisStart};
+//! use async_trait::async_trait;
+//! use std::sync::Arc;
+//!
+//! struct MyHandler;
+//!
+//! #[async_trait]
+//! impl AriEventHandler for MyHandler {
+//!     async fn on_stasis_start(&self, event: StasisStart) {
+//!         println!("call {} entered Stasis", event.channel.id);
+//!     }
+//!
+//!     async fn on_channel_dtmf_received(&self, event: ChannelDtmfReceived) {
+//!         println!("call {} pressed {}", event.channel.id, event.digit);
+//!     }
+//! }
+//!
+//! # async fn example() -> asterisk_ari_client_rs::errors::Result<()> {
+//! let client = AriClient::new("http://localhost:8088/ari".into(), "asterisk".into(), "asterisk".into());
+//! client.add_event_handler(Arc::new(MyHandler), HandlerConcurrency::PerChannel);
+//! client.ari_processing_loop(vec!["my-ast-app".into()]).await
+//! # }
+//! ```
+
+use crate::client::AriClient;
+use crate::dispatch::{default_key, KeyFn, KeyedDispatcher};
+use crate::models::events::*;
+use crate::sink::{BackpressurePolicy, EventSink};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Handler of ARI events, all methods are no-op by default.
+#[async_trait]
+pub trait AriEventHandler: Send + Sync {
+    /// Invoked for every event, default implementation calls respective on_* method.
+    async fn on_event(&self, event: AriEvent) {
+        match event {
+            AriEvent::StasisStart(event) => self.on_stasis_start(event).await,
+            AriEvent::ChannelDtmfReceived(event) => self.on_channel_dtmf_received(event).await,
+            AriEvent::ChannelHangupRequest(event) => self.on_channel_hangup_request(event).await,
+            AriEvent::StasisEnd(event) => self.on_stasis_end(event).await,
+            AriEvent::ChannelTalkingFinished(event) => {
+                self.on_channel_talking_finished(event).await
+            }
+            AriEvent::ChannelTalkingStarted(event) => self.on_channel_talking_started(event).await,
+            AriEvent::ChannelDestroyed(event) => self.on_channel_destroyed(event).await,
+            AriEvent::PlaybackStarted(event) => self.on_playback_started(event).await,
+            AriEvent::PlaybackFinished(event) => self.on_playback_finished(event).await,
+            AriEvent::ChannelStateChange(event) => self.on_channel_state_change(event).await,
+            AriEvent::ChannelVarset(event) => self.on_channel_var_set(event).await,
+            AriEvent::RecordingStarted(event) => self.on_recording_started(event).await,
+            AriEvent::RecordingFinished(event) => self.on_recording_finished(event).await,
+            AriEvent::BridgeCreated(event) => self.on_bridge_created(event).await,
+            AriEvent::BridgeDestroyed(event) => self.on_bridge_destroyed(event).await,
+            AriEvent::ChannelEnteredBridge(event) => self.on_channel_entered_bridge(event).await,
+            AriEvent::ChannelLeftBridge(event) => self.on_channel_left_bridge(event).await,
+        }
+    }
+
+    async fn on_stasis_start(&self, _event: StasisStart) {}
+
+    async fn on_channel_dtmf_received(&self, _event: ChannelDtmfReceived) {}
+
+    async fn on_channel_hangup_request(&self, _event: ChannelHangupRequest) {}
+
+    async fn on_stasis_end(&self, _event: StasisEnd) {}
+
+    async fn on_channel_talking_finished(&self, _event: ChannelTalkingFinished) {}
+
+    async fn on_channel_talking_started(&self, _event: ChannelTalkingStarted) {}
+
+    async fn on_channel_destroyed(&self, _event: ChannelDestroyed) {}
+
+    async fn on_playback_started(&self, _event: PlaybackStarted) {}
+
+    async fn on_playback_finished(&self, _event: PlaybackFinished) {}
+
+    async fn on_channel_state_change(&self, _event: ChannelStateChange) {}
+
+    async fn on_channel_var_set(&self, _event: ChannelVarset) {}
+
+    async fn on_recording_started(&self, _event: RecordingStarted) {}
+
+    async fn on_recording_finished(&self, _event: RecordingFinished) {}
+
+    async fn on_bridge_created(&self, _event: BridgeCreated) {}
+
+    async fn on_bridge_destroyed(&self, _event: BridgeDestroyed) {}
+
+    async fn on_channel_entered_bridge(&self, _event: ChannelEnteredBridge) {}
+
+    async fn on_channel_left_bridge(&self, _event: ChannelLeftBridge) {}
+}
+
+/// How [HandlerDispatcher] invokes the handler.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HandlerConcurrency {
+    /// All events are handled one by one in order they were received.
+    Sequential,
+    /// Events of the same channel are handled one by one in order they were received,
+    /// events of different channels in parallel. Bridge events without channel are handled the same way
+    /// per bridge, remaining events (not related to any channel or bridge) sequentially.
+    #[default]
+    PerChannel,
+}
+
+/// [EventSink] invoking [AriEventHandler] in background tasks (see [KeyedDispatcher]), i.e. handler never blocks
+/// websocket processing. Panicking handler is logged, processing continues with the next event.
+/// Unlike [KeyedDispatcher] default, queues are unbounded, i.e. no event is dropped.
+pub struct HandlerDispatcher {
+    dispatcher: KeyedDispatcher,
+}
+
+impl HandlerDispatcher {
+    pub fn new(handler: Arc<dyn AriEventHandler>, concurrency: HandlerConcurrency) -> Self {
+        let key: KeyFn = match concurrency {
+            HandlerConcurrency::Sequential => |_| None,
+            HandlerConcurrency::PerChannel => default_key,
+        };
+        let dispatcher = KeyedDispatcher::new(move |event| {
+            let handler = handler.clone();
+            async move { handler.on_event(event).await }
+        })
+        .key(key)
+        // handler expects every event (e.g. StasisEnd), i.e. queues are not bounded
+        .policy(BackpressurePolicy::default());
+        HandlerDispatcher { dispatcher }
+    }
+}
+
+#[async_trait]
+impl EventSink for HandlerDispatcher {
+    async fn deliver(&self, event: &AriEvent) {
+        self.dispatcher.deliver(event).await;
+    }
+
+    async fn flush(&self) {
+        self.dispatcher.flush().await;
+    }
+}
+
+impl AriClient {
+    /// Register handler invoked for all ARI events received by this client (and its clones).
+    pub fn add_event_handler(
+        &self,
+        handler: Arc<dyn AriEventHandler>,
+        concurrency: HandlerConcurrency,
+    ) {
+        self.add_event_sink(Arc::new(HandlerDispatcher::new(handler, concurrency)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::{dtmf, stasis_end};
+    use std::sync::Mutex;
+    use tokio::time::{sleep, Duration, Instant};
+
+    /// records handled events, digits of channel "slow" take 100ms to handle
+    #[derive(Default)]
+    struct RecordingHandler {
+        handled: Mutex<Vec<(String, String, Duration)>>,
+        started: Mutex<Option<Instant>>,
+    }
+
+    impl RecordingHandler {
+        fn record(&self, channel_id: &str, what: &str) {
+            let elapsed = self.started.lock().unwrap().unwrap().elapsed();
+            self.handled
+                .lock()
+                .unwrap()
+                .push((channel_id.into(), what.into(), elapsed));
+        }
+    }
+
+    #[async_trait]
+    impl AriEventHandler for RecordingHandler {
+        async fn on_channel_dtmf_received(&self, event: ChannelDtmfReceived) {
+            if event.channel.id == "slow" {
+                sleep(Duration::from_millis(100)).await;
+            }
+            if event.digit == "#" {
+                panic!("handler failure");
+            }
+            self.record(&event.channel.id, &event.digit);
+        }
+
+        async fn on_stasis_end(&self, event: StasisEnd) {
+            self.record(&event.channel.id, "end");
+        }
+    }
+
+    async fn dispatch(concurrency: HandlerConcurrency) -> Vec<(String, String, Duration)> {
+        let client = AriClient::new("http://localhost:8088/ari".into(), "".into(), "".into());
+        let handler = Arc::new(RecordingHandler::default());
+        *handler.started.lock().unwrap() = Some(Instant::now());
+        client.add_event_handler(handler.clone(), concurrency);
+
+        for event in [
+            dtmf("slow", "1"),
+            dtmf("fast", "4"),
+            dtmf("slow", "#"),
+            dtmf("slow", "2"),
+            dtmf("fast", "5"),
+            stasis_end("slow"),
+            // channel re-entering Stasis is handled after its previous events
+            dtmf("slow", "3"),
+        ] {
+            client.dispatch_event(event).await;
+        }
+        sleep(Duration::from_secs(1)).await;
+
+        let handled = handler.handled.lock().unwrap().clone();
+        handled
+    }
+
+    fn handled(events: &[(String, String, Duration)], channel_id: &str) -> Vec<(String, u64)> {
+        events
+            .iter()
+            .filter(|(id, _, _)| id == channel_id)
+            .map(|(_, what, elapsed)| (what.clone(), elapsed.as_millis() as u64))
+            .collect()
+    }
+
+    // cargo test -- --show-output test_handler_per_channel
+    #[tokio::test(start_paused = true)]
+    async fn test_handler_per_channel() {
+        let events = dispatch(HandlerConcurrency::PerChannel).await;
+        // fast channel is not delayed by the slow one, panicking handler does not stop the processing
+        assert_eq!(
+            handled(&events, "fast"),
+            vec![("4".into(), 0), ("5".into(), 0)]
+        );
+        assert_eq!(
+            handled(&events, "slow"),
+            vec![
+                ("1".into(), 100),
+                ("2".into(), 300),
+                ("end".into(), 300),
+                ("3".into(), 400)
+            ]
+        );
+    }
+
+    // cargo test -- --show-output test_handler_sequential
+    #[tokio::test(start_paused = true)]
+    async fn test_handler_sequential() {
+        let events = dispatch(HandlerConcurrency::Sequential).await;
+        assert_eq!(
+            handled(&events, "fast"),
+            vec![("4".into(), 100), ("5".into(), 300)]
+        );
+        assert_eq!(
+            handled(&events, "slow"),
+            vec![
+                ("1".into(), 100),
+                ("2".into(), 300),
+                ("end".into(), 300),
+                ("3".into(), 400)
+            ]
+        );
+    }
+}
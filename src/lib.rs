@@ -1,4 +1,15 @@
 pub mod apis;
+pub mod app;
 pub mod client;
+pub mod connection;
+pub mod dispatch;
+pub mod dtmf;
 pub mod errors;
+pub mod handler;
 pub mod models;
+pub mod replay;
+pub mod retry;
+pub mod sink;
+pub mod state;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
@@ -0,0 +1,45 @@
+#[cfg(feature = "parse-event-datetimes")]
+use crate::models::channels::ari_date_format;
+#[cfg(feature = "parse-event-datetimes")]
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+
+/// The merging of media from one or more channels.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bridge {
+    /// Unique identifier for this bridge.
+    pub id: String,
+
+    /// Name of the current bridging technology (e.g. simple_bridge, softmix).
+    pub technology: String,
+
+    /// Type of bridge technology (mixing, holding, ...).
+    pub bridge_type: String,
+
+    /// Bridging class (e.g. stasis, base).
+    pub bridge_class: String,
+
+    /// Entity that created the bridge.
+    pub creator: String,
+
+    /// Name the creator gave the bridge.
+    pub name: String,
+
+    /// Ids of channels in this bridge.
+    pub channels: Vec<String>,
+
+    /// The video mode the bridge is using (none, talker or sfu).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video_mode: Option<String>,
+
+    /// The id of the channel that is the source of video in this bridge, if one exists.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video_source_id: Option<String>,
+
+    #[cfg(feature = "parse-event-datetimes")]
+    #[serde(with = "ari_date_format")]
+    pub creationtime: DateTime<FixedOffset>,
+
+    #[cfg(not(feature = "parse-event-datetimes"))]
+    pub creationtime: String,
+}
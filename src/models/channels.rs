@@ -1,9 +1,9 @@
 use core::fmt;
 
-use serde::{Deserialize, Serialize};
-// https://serde.rs/custom-date-format.html
 #[cfg(feature = "parse-event-datetimes")]
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Channel {
@@ -13,7 +13,7 @@ pub struct Channel {
     /// Name of the channel (i.e. SIP/foo-0000a7e3)
     pub name: String,
 
-    pub state: String,
+    pub state: ChannelState,
 
     pub caller: CallerId,
 
@@ -25,7 +25,7 @@ pub struct Channel {
 
     #[cfg(feature = "parse-event-datetimes")]
     #[serde(with = "ari_date_format")]
-    pub creationtime: DateTime<Utc>,
+    pub creationtime: DateTime<FixedOffset>,
 
     #[cfg(not(feature = "parse-event-datetimes"))]
     pub creationtime: String,
@@ -33,28 +33,35 @@ pub struct Channel {
     /// The default spoken language
     pub language: Option<String>,
 
-    /// Channel variables
+    /// Channel variables configured by channelvars option in ari.conf, see [Channel::channel_var].
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub channelvars: Option<serde_json::Value>,
+    pub channelvars: Option<HashMap<String, String>>,
+}
+
+impl Channel {
+    /// Value of channel variable delivered with the event (see channelvars option in ari.conf).
+    /// None when the variable is not configured to be delivered, use
+    /// [get_variable](crate::apis::channels::ChannelsAPI::get_variable) to fetch any variable.
+    pub fn channel_var(&self, name: &str) -> Option<&str> {
+        self.channelvars
+            .as_ref()
+            .and_then(|vars| vars.get(name))
+            .map(String::as_str)
+    }
 }
 
+/// (De)serialization of ARI timestamps preserving their UTC offset. Asterisk uses
+/// offset without colon (2021-01-07T21:12:57.268+0100), RFC 3339 form (+01:00) is accepted as well.
 #[cfg(feature = "parse-event-datetimes")]
 pub mod ari_date_format {
-    use chrono::{DateTime, TimeZone, Utc};
+    use chrono::{DateTime, FixedOffset};
     use serde::{self, Deserialize, Deserializer, Serializer};
 
-    // see https://docs.rs/chrono/0.4.19/chrono/format/strftime/index.html#specifiers
-    // same as %+ using longer format for better readability
-    const FORMAT: &'static str = "%Y-%m-%dT%H:%M:%S%.f%:z";
-
-    // The signature of a serialize_with function must follow the pattern:
-    //
-    //    fn serialize<S>(&T, S) -> Result<S::Ok, S::Error>
-    //    where
-    //        S: Serializer
-    //
-    // although it may also be generic over the input types T.
-    pub fn serialize<S>(date: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    // see https://docs.rs/chrono/0.4.31/chrono/format/strftime/index.html#specifiers
+    const FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f%z";
+    const FORMAT_COLON: &str = "%Y-%m-%dT%H:%M:%S%.f%:z";
+
+    pub fn serialize<S>(date: &DateTime<FixedOffset>, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
@@ -62,20 +69,17 @@ pub mod ari_date_format {
         serializer.serialize_str(&s)
     }
 
-    // The signature of a deserialize_with function must follow the pattern:
-    //
-    //    fn deserialize<'de, D>(D) -> Result<T, D::Error>
-    //    where
-    //        D: Deserializer<'de>
-    //
-    // although it may also be generic over the output types T.
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<FixedOffset>, D::Error>
     where
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        Utc.datetime_from_str(&s, FORMAT)
-            .map_err(serde::de::Error::custom)
+        parse(&s).map_err(serde::de::Error::custom)
+    }
+
+    pub fn parse(s: &str) -> chrono::ParseResult<DateTime<FixedOffset>> {
+        DateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f%z")
+            .or_else(|_| DateTime::parse_from_str(s, FORMAT_COLON))
     }
 }
 
@@ -207,6 +211,7 @@ pub struct Variable {
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
 pub enum Direction {
     #[default]
     None,
@@ -225,3 +230,402 @@ impl fmt::Display for Direction {
         write!(f, "{}", str)
     }
 }
+
+/// Options of [snoop](crate::apis::channels::ChannelsAPI::snoop).
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct SnoopOptions {
+    pub(crate) spy: Direction,
+    pub(crate) whisper: Direction,
+    pub(crate) app_args: Option<String>,
+    pub(crate) snoop_id: Option<String>,
+}
+
+impl SnoopOptions {
+    /// Direction of audio to spy on, default none.
+    pub fn spy(mut self, spy: Direction) -> Self {
+        self.spy = spy;
+        self
+    }
+
+    /// Direction of audio to whisper into, default none.
+    pub fn whisper(mut self, whisper: Direction) -> Self {
+        self.whisper = whisper;
+        self
+    }
+
+    /// Application arguments passed in StasisStart event of the snooping channel.
+    pub fn app_args(mut self, app_args: impl Into<String>) -> Self {
+        self.app_args = Some(app_args.into());
+        self
+    }
+
+    /// Unique id assigned to the snooping channel.
+    pub fn snoop_id(mut self, snoop_id: impl Into<String>) -> Self {
+        self.snoop_id = Some(snoop_id.into());
+        self
+    }
+}
+
+string_enum! {
+    /// State of the channel.
+    pub enum ChannelState {
+        Down => "Down",
+        /// Line is reserved (busy, not in use).
+        Rsrvd => "Rsrvd",
+        OffHook => "OffHook",
+        /// Digits have been dialed.
+        Dialing => "Dialing",
+        /// Remote end is ringing (outbound channel).
+        Ring => "Ring",
+        /// Local end is ringing (inbound channel).
+        Ringing => "Ringing",
+        Up => "Up",
+        Busy => "Busy",
+        DialingOffhook => "Dialing Offhook",
+        PreRing => "Pre-ring",
+        Unknown => "Unknown",
+    }
+}
+
+macro_rules! hangup_causes {
+    ($($variant:ident => ($code:literal, $name:literal, $description:literal),)*) => {
+        /// Q.850 cause of the channel hangup, see
+        /// [ChannelDestroyed](crate::models::events::ChannelDestroyed).
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum HangupCause {
+            $($variant,)*
+            /// Cause code not known to this library.
+            Other(i64),
+        }
+
+        impl HangupCause {
+            pub fn code(&self) -> i64 {
+                match self {
+                    $(HangupCause::$variant => $code,)*
+                    HangupCause::Other(code) => *code,
+                }
+            }
+
+            /// Asterisk name of the cause (AST_CAUSE_ prefix omitted), e.g. NORMAL_CLEARING.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(HangupCause::$variant => $name,)*
+                    HangupCause::Other(_) => "UNKNOWN",
+                }
+            }
+
+            pub fn description(&self) -> &'static str {
+                match self {
+                    $(HangupCause::$variant => $description,)*
+                    HangupCause::Other(_) => "Unknown cause",
+                }
+            }
+        }
+
+        impl From<i64> for HangupCause {
+            fn from(code: i64) -> Self {
+                match code {
+                    $($code => HangupCause::$variant,)*
+                    _ => HangupCause::Other(code),
+                }
+            }
+        }
+    };
+}
+
+hangup_causes! {
+    NotDefined => (0, "NOT_DEFINED", "Not defined"),
+    Unallocated => (1, "UNALLOCATED", "Unallocated (unassigned) number"),
+    NoRouteTransitNet => (2, "NO_ROUTE_TRANSIT_NET", "No route to specified transit network"),
+    NoRouteDestination => (3, "NO_ROUTE_DESTINATION", "No route to destination"),
+    MisdialledTrunkPrefix => (5, "MISDIALLED_TRUNK_PREFIX", "Misdialled trunk prefix"),
+    ChannelUnacceptable => (6, "CHANNEL_UNACCEPTABLE", "Channel unacceptable"),
+    CallAwardedDelivered => (7, "CALL_AWARDED_DELIVERED", "Call awarded and being delivered in an established channel"),
+    PreEmpted => (8, "PRE_EMPTED", "Preemption"),
+    NumberPortedNotHere => (14, "NUMBER_PORTED_NOT_HERE", "Number ported but not found here"),
+    NormalClearing => (16, "NORMAL_CLEARING", "Normal call clearing"),
+    UserBusy => (17, "USER_BUSY", "User busy"),
+    NoUserResponse => (18, "NO_USER_RESPONSE", "No user responding"),
+    NoAnswer => (19, "NO_ANSWER", "User alerting, no answer"),
+    SubscriberAbsent => (20, "SUBSCRIBER_ABSENT", "Subscriber absent"),
+    CallRejected => (21, "CALL_REJECTED", "Call rejected"),
+    NumberChanged => (22, "NUMBER_CHANGED", "Number changed"),
+    RedirectedToNewDestination => (23, "REDIRECTED_TO_NEW_DESTINATION", "Redirected to new destination"),
+    AnsweredElsewhere => (26, "ANSWERED_ELSEWHERE", "Answered elsewhere"),
+    DestinationOutOfOrder => (27, "DESTINATION_OUT_OF_ORDER", "Destination out of order"),
+    InvalidNumberFormat => (28, "INVALID_NUMBER_FORMAT", "Invalid number format"),
+    FacilityRejected => (29, "FACILITY_REJECTED", "Facility rejected"),
+    ResponseToStatusEnquiry => (30, "RESPONSE_TO_STATUS_ENQUIRY", "Response to STATus ENQuiry"),
+    NormalUnspecified => (31, "NORMAL_UNSPECIFIED", "Normal, unspecified"),
+    NormalCircuitCongestion => (34, "NORMAL_CIRCUIT_CONGESTION", "Circuit/channel congestion"),
+    NetworkOutOfOrder => (38, "NETWORK_OUT_OF_ORDER", "Network out of order"),
+    NormalTemporaryFailure => (41, "NORMAL_TEMPORARY_FAILURE", "Temporary failure"),
+    SwitchCongestion => (42, "SWITCH_CONGESTION", "Switching equipment congestion"),
+    AccessInfoDiscarded => (43, "ACCESS_INFO_DISCARDED", "Access information discarded"),
+    RequestedChanUnavail => (44, "REQUESTED_CHAN_UNAVAIL", "Requested channel not available"),
+    FacilityNotSubscribed => (50, "FACILITY_NOT_SUBSCRIBED", "Facility not subscribed"),
+    OutgoingCallBarred => (52, "OUTGOING_CALL_BARRED", "Outgoing call barred"),
+    IncomingCallBarred => (54, "INCOMING_CALL_BARRED", "Incoming call barred"),
+    BearerCapabilityNotAuth => (57, "BEARERCAPABILITY_NOTAUTH", "Bearer capability not authorized"),
+    BearerCapabilityNotAvail => (58, "BEARERCAPABILITY_NOTAVAIL", "Bearer capability not available"),
+    BearerCapabilityNotImpl => (65, "BEARERCAPABILITY_NOTIMPL", "Bearer capability not implemented"),
+    ChanNotImplemented => (66, "CHAN_NOT_IMPLEMENTED", "Channel type not implemented"),
+    FacilityNotImplemented => (69, "FACILITY_NOT_IMPLEMENTED", "Facility not implemented"),
+    InvalidCallReference => (81, "INVALID_CALL_REFERENCE", "Invalid call reference value"),
+    IncompatibleDestination => (88, "INCOMPATIBLE_DESTINATION", "Incompatible destination"),
+    InvalidMsgUnspecified => (95, "INVALID_MSG_UNSPECIFIED", "Invalid message, unspecified"),
+    MandatoryIeMissing => (96, "MANDATORY_IE_MISSING", "Mandatory information element is missing"),
+    MessageTypeNonexist => (97, "MESSAGE_TYPE_NONEXIST", "Message type nonexistent or not implemented"),
+    WrongMessage => (98, "WRONG_MESSAGE", "Message not compatible with call state or message type nonexistent"),
+    IeNonexist => (99, "IE_NONEXIST", "Information element nonexistent or not implemented"),
+    InvalidIeContents => (100, "INVALID_IE_CONTENTS", "Invalid information element contents"),
+    WrongCallState => (101, "WRONG_CALL_STATE", "Message not compatible with call state"),
+    RecoveryOnTimerExpire => (102, "RECOVERY_ON_TIMER_EXPIRE", "Recovery on timer expiry"),
+    MandatoryIeLengthError => (103, "MANDATORY_IE_LENGTH_ERROR", "Mandatory information element length error"),
+    ProtocolError => (111, "PROTOCOL_ERROR", "Protocol error, unspecified"),
+    Interworking => (127, "INTERWORKING", "Interworking, unspecified"),
+}
+
+impl fmt::Display for HangupCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.description(), self.code())
+    }
+}
+
+impl Serialize for HangupCause {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for HangupCause {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(HangupCause::from(i64::deserialize(deserializer)?))
+    }
+}
+
+/// Options of [originate](crate::apis::channels::ChannelsAPI::originate). Either dialplan
+/// location (extension/context/priority) or Stasis application must be set.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct OriginateOptions {
+    pub(crate) extension: Option<String>,
+    pub(crate) context: Option<String>,
+    pub(crate) priority: Option<i64>,
+    pub(crate) label: Option<String>,
+    pub(crate) app: Option<String>,
+    pub(crate) app_args: Option<String>,
+    pub(crate) caller_id: Option<String>,
+    pub(crate) timeout: Option<i64>,
+    pub(crate) channel_id: Option<String>,
+    pub(crate) other_channel_id: Option<String>,
+    pub(crate) originator: Option<String>,
+    pub(crate) formats: Option<String>,
+    pub(crate) variables: HashMap<String, String>,
+}
+
+impl OriginateOptions {
+    /// Dialplan extension to connect the channel to once answered.
+    pub fn extension(mut self, extension: impl Into<String>) -> Self {
+        self.extension = Some(extension.into());
+        self
+    }
+
+    pub fn context(mut self, context: impl Into<String>) -> Self {
+        self.context = Some(context.into());
+        self
+    }
+
+    pub fn priority(mut self, priority: i64) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Dialplan label, overrides priority.
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Stasis application to put the channel into once answered.
+    pub fn app(mut self, app: impl Into<String>) -> Self {
+        self.app = Some(app.into());
+        self
+    }
+
+    /// Application arguments (comma separated) passed in StasisStart event.
+    pub fn app_args(mut self, app_args: impl Into<String>) -> Self {
+        self.app_args = Some(app_args.into());
+        self
+    }
+
+    /// Caller id, e.g. "Alice" <1001>
+    pub fn caller_id(mut self, caller_id: impl Into<String>) -> Self {
+        self.caller_id = Some(caller_id.into());
+        self
+    }
+
+    /// Timeout in seconds before giving up dialing, Asterisk default is 30.
+    pub fn timeout(mut self, seconds: i64) -> Self {
+        self.timeout = Some(seconds);
+        self
+    }
+
+    /// Unique id assigned to the new channel.
+    pub fn channel_id(mut self, channel_id: impl Into<String>) -> Self {
+        self.channel_id = Some(channel_id.into());
+        self
+    }
+
+    /// Unique id assigned to the second (local) channel.
+    pub fn other_channel_id(mut self, other_channel_id: impl Into<String>) -> Self {
+        self.other_channel_id = Some(other_channel_id.into());
+        self
+    }
+
+    /// Id of the channel responsible for the origination.
+    pub fn originator(mut self, originator: impl Into<String>) -> Self {
+        self.originator = Some(originator.into());
+        self
+    }
+
+    /// Comma separated list of allowed formats, e.g. ulaw,slin16.
+    pub fn formats(mut self, formats: impl Into<String>) -> Self {
+        self.formats = Some(formats.into());
+        self
+    }
+
+    /// Channel variable set on the new channel.
+    pub fn variable(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+
+    /// Channel variables set on the new channel.
+    pub fn variables(mut self, variables: HashMap<String, String>) -> Self {
+        self.variables.extend(variables);
+        self
+    }
+}
+
+/// Options of [create_channel](crate::apis::channels::ChannelsAPI::create_channel).
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct CreateChannelOptions {
+    pub(crate) app_args: Option<String>,
+    pub(crate) channel_id: Option<String>,
+    pub(crate) other_channel_id: Option<String>,
+    pub(crate) originator: Option<String>,
+    pub(crate) formats: Option<String>,
+    pub(crate) variables: HashMap<String, String>,
+}
+
+impl CreateChannelOptions {
+    /// Application arguments (comma separated) passed in StasisStart event.
+    pub fn app_args(mut self, app_args: impl Into<String>) -> Self {
+        self.app_args = Some(app_args.into());
+        self
+    }
+
+    /// Unique id assigned to the new channel.
+    pub fn channel_id(mut self, channel_id: impl Into<String>) -> Self {
+        self.channel_id = Some(channel_id.into());
+        self
+    }
+
+    /// Unique id assigned to the second (local) channel.
+    pub fn other_channel_id(mut self, other_channel_id: impl Into<String>) -> Self {
+        self.other_channel_id = Some(other_channel_id.into());
+        self
+    }
+
+    /// Id of the channel responsible for the creation.
+    pub fn originator(mut self, originator: impl Into<String>) -> Self {
+        self.originator = Some(originator.into());
+        self
+    }
+
+    /// Comma separated list of allowed formats, e.g. ulaw,slin16.
+    pub fn formats(mut self, formats: impl Into<String>) -> Self {
+        self.formats = Some(formats.into());
+        self
+    }
+
+    /// Channel variable set on the new channel.
+    pub fn variable(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+
+    /// Channel variables set on the new channel.
+    pub fn variables(mut self, variables: HashMap<String, String>) -> Self {
+        self.variables.extend(variables);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // cargo test -- --show-output test_channel_state
+    #[test]
+    fn test_channel_state() {
+        let state: ChannelState = serde_json::from_str("\"Dialing Offhook\"").unwrap();
+        assert_eq!(state, ChannelState::DialingOffhook);
+        assert_eq!(
+            serde_json::to_string(&ChannelState::PreRing).unwrap(),
+            "\"Pre-ring\""
+        );
+
+        let state: ChannelState = serde_json::from_str("\"Mute\"").unwrap();
+        assert_eq!(state, ChannelState::Other("Mute".into()));
+        assert_eq!(serde_json::to_string(&state).unwrap(), "\"Mute\"");
+    }
+
+    // cargo test -- --show-output test_ari_date_format
+    #[cfg(feature = "parse-event-datetimes")]
+    #[test]
+    fn test_ari_date_format() {
+        let without_colon = ari_date_format::parse("2021-01-07T21:12:57.268+0100").unwrap();
+        let with_colon = ari_date_format::parse("2021-01-07T21:12:57.268+01:00").unwrap();
+        assert_eq!(without_colon, with_colon);
+        assert_eq!(without_colon.offset().local_minus_utc(), 3600);
+        assert_eq!(without_colon.to_rfc3339(), "2021-01-07T21:12:57.268+01:00");
+
+        let utc = ari_date_format::parse("2021-01-07T20:12:58.268+0000").unwrap();
+        assert_eq!((utc - without_colon).num_milliseconds(), 1000);
+
+        #[derive(Serialize, Deserialize)]
+        struct Timestamp(#[serde(with = "ari_date_format")] DateTime<FixedOffset>);
+        assert_eq!(
+            serde_json::to_string(&Timestamp(without_colon)).unwrap(),
+            "\"2021-01-07T21:12:57.268+0100\""
+        );
+    }
+
+    // cargo test -- --show-output test_channel_vars
+    #[test]
+    fn test_channel_vars() {
+        let json = r#"{"id": "1610050377.0", "name": "SIP/1004-00000000", "state": "Up",
+            "caller": {"name": "", "number": "1004"}, "connected": {"name": "", "number": ""},
+            "accountcode": "", "dialplan": {"context": "internal", "exten": "158", "priority": 10, "app_name": "Stasis", "app_data": ""},
+            "creationtime": "2021-01-07T21:12:57.267+0100", "language": "en",
+            "channelvars": {"CDR(userfield)": "vip", "CUSTOMER_ID": "42"}}"#;
+        let channel: Channel = serde_json::from_str(json).unwrap();
+        assert_eq!(channel.channel_var("CUSTOMER_ID"), Some("42"));
+        assert_eq!(channel.channel_var("CDR(userfield)"), Some("vip"));
+        assert_eq!(channel.channel_var("UNKNOWN"), None);
+    }
+
+    // cargo test -- --show-output test_hangup_cause
+    #[test]
+    fn test_hangup_cause() {
+        let cause: HangupCause = serde_json::from_str("16").unwrap();
+        assert_eq!(cause, HangupCause::NormalClearing);
+        assert_eq!(cause.name(), "NORMAL_CLEARING");
+        assert_eq!(cause.to_string(), "Normal call clearing (16)");
+
+        let cause: HangupCause = serde_json::from_str("200").unwrap();
+        assert_eq!(cause, HangupCause::Other(200));
+        assert_eq!(serde_json::to_string(&cause).unwrap(), "200");
+    }
+}
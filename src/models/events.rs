@@ -1,33 +1,23 @@
 use super::playbacks::Playback;
+use crate::models::bridges::Bridge;
 #[cfg(feature = "parse-event-datetimes")]
 use crate::models::channels::ari_date_format;
-use crate::models::channels::Channel;
+use crate::models::channels::{Channel, HangupCause};
 use crate::models::recordings::Recording;
 #[cfg(feature = "parse-event-datetimes")]
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset};
 use serde::{Deserialize, Serialize};
 
-// TBD: Event extends Message and all event types extend event.
-// Since rust does not support inheritance we need to figure out how
-// how composition would work with serde. For now just duplicating the stuff
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct Message {
-    /// Indicates the type of this message.
-    #[serde(rename = "type")]
-    pub r#type: String,
+/// Type of event timestamps, see feature parse-event-datetimes.
+#[cfg(feature = "parse-event-datetimes")]
+pub type Timestamp = DateTime<FixedOffset>;
 
-    /// The unique ID for the Asterisk instance that raised this event.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub asterisk_id: Option<String>,
-}
+#[cfg(not(feature = "parse-event-datetimes"))]
+pub type Timestamp = String;
 
+/// Fields common to all events, flattened into each event struct.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct Event {
-    /// Indicates the type of this message.
-    #[serde(rename = "type")]
-    pub r#type: String,
-
+pub struct EventEnvelope {
     /// The unique ID for the Asterisk instance that raised this event.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub asterisk_id: Option<String>,
@@ -35,31 +25,15 @@ pub struct Event {
     /// Name of the application receiving the event.
     pub application: String,
 
-    /// Time at which this event was created.
-    #[cfg(feature = "parse-event-datetimes")]
-    #[serde(with = "ari_date_format")]
-    pub timestamp: DateTime<Utc>,
-
-    #[cfg(not(feature = "parse-event-datetimes"))]
-    pub timestamp: String,
+    /// Time at which this event was created. E.g. 2020-11-22T20:12:51.214+0000
+    #[cfg_attr(feature = "parse-event-datetimes", serde(with = "ari_date_format"))]
+    pub timestamp: Timestamp,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct StasisStart {
-    /// The unique ID for the Asterisk instance that raised this event.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub asterisk_id: Option<String>,
-
-    /// Name of the application receiving the event.
-    pub application: String,
-
-    /// Time at which this event was created. E.g. 2020-11-22T20:12:51.214+0000
-    #[cfg(feature = "parse-event-datetimes")]
-    #[serde(with = "ari_date_format")]
-    pub timestamp: DateTime<Utc>,
-
-    #[cfg(not(feature = "parse-event-datetimes"))]
-    pub timestamp: String,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
 
     /// Arguments to the application.
     pub args: Vec<String>,
@@ -74,20 +48,8 @@ pub struct StasisStart {
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ChannelDtmfReceived {
-    /// The unique ID for the Asterisk instance that raised this event.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub asterisk_id: Option<String>,
-
-    /// Name of the application receiving the event.
-    pub application: String,
-
-    /// Time at which this event was created.
-    #[cfg(feature = "parse-event-datetimes")]
-    #[serde(with = "ari_date_format")]
-    pub timestamp: DateTime<Utc>,
-
-    #[cfg(not(feature = "parse-event-datetimes"))]
-    pub timestamp: String,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
 
     /// DTMF digit received (0-9, A-E, # or *).
     pub digit: String,
@@ -101,24 +63,12 @@ pub struct ChannelDtmfReceived {
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ChannelHangupRequest {
-    /// The unique ID for the Asterisk instance that raised this event.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub asterisk_id: Option<String>,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
 
-    /// Name of the application receiving the event.
-    pub application: String,
-
-    /// Time at which this event was created.
-    #[cfg(feature = "parse-event-datetimes")]
-    #[serde(with = "ari_date_format")]
-    pub timestamp: DateTime<Utc>,
-
-    #[cfg(not(feature = "parse-event-datetimes"))]
-    pub timestamp: String,
-
-    /// Integer representation of the cause of the hangup.
+    /// Cause of the hangup.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub cause: Option<i64>,
+    pub cause: Option<HangupCause>,
 
     /// Whether the hangup request was a soft hangup request.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -130,20 +80,8 @@ pub struct ChannelHangupRequest {
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct StasisEnd {
-    /// The unique ID for the Asterisk instance that raised this event.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub asterisk_id: Option<String>,
-
-    /// Name of the application receiving the event.
-    pub application: String,
-
-    /// Time at which this event was created.
-    #[cfg(feature = "parse-event-datetimes")]
-    #[serde(with = "ari_date_format")]
-    pub timestamp: DateTime<Utc>,
-
-    #[cfg(not(feature = "parse-event-datetimes"))]
-    pub timestamp: String,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
 
     /// Channel.
     pub channel: Channel,
@@ -151,20 +89,8 @@ pub struct StasisEnd {
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ChannelTalkingFinished {
-    /// The unique ID for the Asterisk instance that raised this event.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub asterisk_id: Option<String>,
-
-    /// Name of the application receiving the event.
-    pub application: String,
-
-    /// Time at which this event was created.
-    #[cfg(feature = "parse-event-datetimes")]
-    #[serde(with = "ari_date_format")]
-    pub timestamp: DateTime<Utc>,
-
-    #[cfg(not(feature = "parse-event-datetimes"))]
-    pub timestamp: String,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
 
     /// The channel on which talking completed.
     pub channel: Channel,
@@ -175,20 +101,8 @@ pub struct ChannelTalkingFinished {
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ChannelTalkingStarted {
-    /// The unique ID for the Asterisk instance that raised this event.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub asterisk_id: Option<String>,
-
-    /// Name of the application receiving the event.
-    pub application: String,
-
-    /// Time at which this event was created.
-    #[cfg(feature = "parse-event-datetimes")]
-    #[serde(with = "ari_date_format")]
-    pub timestamp: DateTime<Utc>,
-
-    #[cfg(not(feature = "parse-event-datetimes"))]
-    pub timestamp: String,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
 
     /// The channel on which talking started.
     pub channel: Channel,
@@ -196,23 +110,11 @@ pub struct ChannelTalkingStarted {
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ChannelDestroyed {
-    /// The unique ID for the Asterisk instance that raised this event.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub asterisk_id: Option<String>,
-
-    /// Name of the application receiving the event.
-    pub application: String,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
 
-    /// Time at which this event was created.
-    #[cfg(feature = "parse-event-datetimes")]
-    #[serde(with = "ari_date_format")]
-    pub timestamp: DateTime<Utc>,
-
-    #[cfg(not(feature = "parse-event-datetimes"))]
-    pub timestamp: String,
-
-    /// Integer representation of the cause of the hangup.
-    pub cause: i64,
+    /// Cause of the hangup.
+    pub cause: HangupCause,
 
     /// Text representation of the cause of the hangup.
     pub cause_txt: String,
@@ -223,20 +125,8 @@ pub struct ChannelDestroyed {
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PlaybackStarted {
-    /// The unique ID for the Asterisk instance that raised this event.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub asterisk_id: Option<String>,
-
-    /// Name of the application receiving the event.
-    pub application: String,
-
-    /// Time at which this event was created.
-    #[cfg(feature = "parse-event-datetimes")]
-    #[serde(with = "ari_date_format")]
-    pub timestamp: DateTime<Utc>,
-
-    #[cfg(not(feature = "parse-event-datetimes"))]
-    pub timestamp: String,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
 
     /// playback resource
     pub playback: Playback,
@@ -244,20 +134,8 @@ pub struct PlaybackStarted {
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PlaybackFinished {
-    /// The unique ID for the Asterisk instance that raised this event.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub asterisk_id: Option<String>,
-
-    /// Name of the application receiving the event.
-    pub application: String,
-
-    /// Time at which this event was created.
-    #[cfg(feature = "parse-event-datetimes")]
-    #[serde(with = "ari_date_format")]
-    pub timestamp: DateTime<Utc>,
-
-    #[cfg(not(feature = "parse-event-datetimes"))]
-    pub timestamp: String,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
 
     /// playback resource
     pub playback: Playback,
@@ -265,20 +143,8 @@ pub struct PlaybackFinished {
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ChannelStateChange {
-    /// The unique ID for the Asterisk instance that raised this event.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub asterisk_id: Option<String>,
-
-    /// Name of the application receiving the event.
-    pub application: String,
-
-    /// Time at which this event was created. E.g. 2020-11-22T20:12:51.214+0000
-    #[cfg(feature = "parse-event-datetimes")]
-    #[serde(with = "ari_date_format")]
-    pub timestamp: DateTime<Utc>,
-
-    #[cfg(not(feature = "parse-event-datetimes"))]
-    pub timestamp: String,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
 
     /// Channel.
     pub channel: Channel,
@@ -286,20 +152,8 @@ pub struct ChannelStateChange {
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ChannelVarset {
-    /// The unique ID for the Asterisk instance that raised this event.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub asterisk_id: Option<String>,
-
-    /// Name of the application receiving the event.
-    pub application: String,
-
-    /// Time at which this event was created. E.g. 2020-11-22T20:12:51.214+0000
-    #[cfg(feature = "parse-event-datetimes")]
-    #[serde(with = "ari_date_format")]
-    pub timestamp: DateTime<Utc>,
-
-    #[cfg(not(feature = "parse-event-datetimes"))]
-    pub timestamp: String,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
 
     /// Channel.
     pub channel: Channel,
@@ -311,48 +165,67 @@ pub struct ChannelVarset {
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct RecordingStarted {
-    /// The unique ID for the Asterisk instance that raised this event.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub asterisk_id: Option<String>,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
 
-    /// Name of the application receiving the event.
-    pub application: String,
-
-    /// Time at which this event was created. E.g. 2020-11-22T20:12:51.214+0000
-    #[cfg(feature = "parse-event-datetimes")]
-    #[serde(with = "ari_date_format")]
-    pub timestamp: DateTime<Utc>,
+    /// Recording.
+    pub recording: Recording,
+}
 
-    #[cfg(not(feature = "parse-event-datetimes"))]
-    pub timestamp: String,
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordingFinished {
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
 
     /// Recording.
     pub recording: Recording,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct RecordingFinished {
-    /// The unique ID for the Asterisk instance that raised this event.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub asterisk_id: Option<String>,
+pub struct BridgeCreated {
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
 
-    /// Name of the application receiving the event.
-    pub application: String,
+    /// Bridge.
+    pub bridge: Bridge,
+}
 
-    /// Time at which this event was created. E.g. 2020-11-22T20:12:51.214+0000
-    #[cfg(feature = "parse-event-datetimes")]
-    #[serde(with = "ari_date_format")]
-    pub timestamp: DateTime<Utc>,
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BridgeDestroyed {
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
+
+    /// Bridge.
+    pub bridge: Bridge,
+}
 
-    #[cfg(not(feature = "parse-event-datetimes"))]
-    pub timestamp: String,
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChannelEnteredBridge {
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
 
-    /// Recording.
-    pub recording: Recording,
+    /// Bridge (including the entering channel).
+    pub bridge: Bridge,
+
+    /// Channel.
+    pub channel: Channel,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChannelLeftBridge {
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
+
+    /// Bridge (without the leaving channel).
+    pub bridge: Bridge,
+
+    /// Channel.
+    pub channel: Channel,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(tag = "type")]
+#[allow(clippy::large_enum_variant)]
 pub enum AriEvent {
     StasisStart(StasisStart),
     ChannelDtmfReceived(ChannelDtmfReceived),
@@ -367,6 +240,117 @@ pub enum AriEvent {
     ChannelVarset(ChannelVarset),
     RecordingStarted(RecordingStarted),
     RecordingFinished(RecordingFinished),
+    BridgeCreated(BridgeCreated),
+    BridgeDestroyed(BridgeDestroyed),
+    ChannelEnteredBridge(ChannelEnteredBridge),
+    ChannelLeftBridge(ChannelLeftBridge),
+}
+
+impl AriEvent {
+    /// Fields common to all events (application, Asterisk id, timestamp).
+    pub fn envelope(&self) -> &EventEnvelope {
+        match self {
+            AriEvent::StasisStart(event) => &event.envelope,
+            AriEvent::ChannelDtmfReceived(event) => &event.envelope,
+            AriEvent::ChannelHangupRequest(event) => &event.envelope,
+            AriEvent::StasisEnd(event) => &event.envelope,
+            AriEvent::ChannelTalkingFinished(event) => &event.envelope,
+            AriEvent::ChannelTalkingStarted(event) => &event.envelope,
+            AriEvent::ChannelDestroyed(event) => &event.envelope,
+            AriEvent::PlaybackStarted(event) => &event.envelope,
+            AriEvent::PlaybackFinished(event) => &event.envelope,
+            AriEvent::ChannelStateChange(event) => &event.envelope,
+            AriEvent::ChannelVarset(event) => &event.envelope,
+            AriEvent::RecordingStarted(event) => &event.envelope,
+            AriEvent::RecordingFinished(event) => &event.envelope,
+            AriEvent::BridgeCreated(event) => &event.envelope,
+            AriEvent::BridgeDestroyed(event) => &event.envelope,
+            AriEvent::ChannelEnteredBridge(event) => &event.envelope,
+            AriEvent::ChannelLeftBridge(event) => &event.envelope,
+        }
+    }
+
+    /// Event type as sent by Asterisk, e.g. StasisStart.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            AriEvent::StasisStart(_) => "StasisStart",
+            AriEvent::ChannelDtmfReceived(_) => "ChannelDtmfReceived",
+            AriEvent::ChannelHangupRequest(_) => "ChannelHangupRequest",
+            AriEvent::StasisEnd(_) => "StasisEnd",
+            AriEvent::ChannelTalkingFinished(_) => "ChannelTalkingFinished",
+            AriEvent::ChannelTalkingStarted(_) => "ChannelTalkingStarted",
+            AriEvent::ChannelDestroyed(_) => "ChannelDestroyed",
+            AriEvent::PlaybackStarted(_) => "PlaybackStarted",
+            AriEvent::PlaybackFinished(_) => "PlaybackFinished",
+            AriEvent::ChannelStateChange(_) => "ChannelStateChange",
+            AriEvent::ChannelVarset(_) => "ChannelVarset",
+            AriEvent::RecordingStarted(_) => "RecordingStarted",
+            AriEvent::RecordingFinished(_) => "RecordingFinished",
+            AriEvent::BridgeCreated(_) => "BridgeCreated",
+            AriEvent::BridgeDestroyed(_) => "BridgeDestroyed",
+            AriEvent::ChannelEnteredBridge(_) => "ChannelEnteredBridge",
+            AriEvent::ChannelLeftBridge(_) => "ChannelLeftBridge",
+        }
+    }
+
+    /// Name of the application receiving the event.
+    pub fn application(&self) -> &str {
+        &self.envelope().application
+    }
+
+    /// Time at which the event was created.
+    pub fn timestamp(&self) -> &Timestamp {
+        &self.envelope().timestamp
+    }
+
+    /// Channel the event relates to. None for events not carrying channel (playbacks, recordings, bridges).
+    pub fn channel(&self) -> Option<&Channel> {
+        match self {
+            AriEvent::StasisStart(event) => Some(&event.channel),
+            AriEvent::ChannelDtmfReceived(event) => Some(&event.channel),
+            AriEvent::ChannelHangupRequest(event) => Some(&event.channel),
+            AriEvent::StasisEnd(event) => Some(&event.channel),
+            AriEvent::ChannelTalkingFinished(event) => Some(&event.channel),
+            AriEvent::ChannelTalkingStarted(event) => Some(&event.channel),
+            AriEvent::ChannelDestroyed(event) => Some(&event.channel),
+            AriEvent::ChannelStateChange(event) => Some(&event.channel),
+            AriEvent::ChannelVarset(event) => Some(&event.channel),
+            AriEvent::ChannelEnteredBridge(event) => Some(&event.channel),
+            AriEvent::ChannelLeftBridge(event) => Some(&event.channel),
+            AriEvent::PlaybackStarted(_)
+            | AriEvent::PlaybackFinished(_)
+            | AriEvent::RecordingStarted(_)
+            | AriEvent::RecordingFinished(_)
+            | AriEvent::BridgeCreated(_)
+            | AriEvent::BridgeDestroyed(_) => None,
+        }
+    }
+
+    /// Bridge the event relates to, None for events not carrying bridge.
+    pub fn bridge(&self) -> Option<&Bridge> {
+        match self {
+            AriEvent::BridgeCreated(event) => Some(&event.bridge),
+            AriEvent::BridgeDestroyed(event) => Some(&event.bridge),
+            AriEvent::ChannelEnteredBridge(event) => Some(&event.bridge),
+            AriEvent::ChannelLeftBridge(event) => Some(&event.bridge),
+            _ => None,
+        }
+    }
+
+    /// Id of the channel the event relates to, for playbacks and recordings derived from target uri (channel:<id>).
+    pub fn channel_id(&self) -> Option<&str> {
+        if let Some(channel) = self.channel() {
+            return Some(&channel.id);
+        }
+        let target_uri = match self {
+            AriEvent::PlaybackStarted(event) => &event.playback.target_uri,
+            AriEvent::PlaybackFinished(event) => &event.playback.target_uri,
+            AriEvent::RecordingStarted(event) => &event.recording.target_uri,
+            AriEvent::RecordingFinished(event) => &event.recording.target_uri,
+            _ => return None,
+        };
+        target_uri.strip_prefix("channel:")
+    }
 }
 
 #[cfg(test)]
@@ -386,18 +370,21 @@ mod tests {
     }
 
     // cargo test --package asterisk-ari-client -- --show-output test_2_parse_stasis_start
-    // throws chrono lib error: no possible date and time matching input
-    // if: "2021-01-07T21:12:57.268+0100 -> "2021-01-07T21:12:57.268+0000 (i.e. TZ 0100 -> 00) it will pass
-    // to be investigated
-    // for now this resulted in feature parse-event-datetimes
-    // i.e. this will pass:
-    //      cargo test --package asterisk-ari-client -- --show-output test_2_parse_stasis_start
-    // but this will not:
-    //      cargo test --features parse-event-datetimes --package asterisk-ari-client -- --show-output test_2_parse_stasis_start
+    // timestamps with non UTC offset (+0100)
     #[test]
     fn test_2_parse_stasis_start() {
         let ari_event: StasisStart = serde_json::from_str(STR_JSON2).unwrap();
         println!("{:#?}", ari_event);
+
+        #[cfg(feature = "parse-event-datetimes")]
+        {
+            assert_eq!(
+                ari_event.envelope.timestamp.offset().local_minus_utc(),
+                3600
+            );
+            let age = ari_event.envelope.timestamp - ari_event.channel.creationtime;
+            assert_eq!(age.num_milliseconds(), 1);
+        }
     }
 
     // cargo test --package asterisk-ari-client -- --show-output test_parse_channel_state_change
@@ -415,4 +402,31 @@ mod tests {
         let ari_event: AriEvent = serde_json::from_str(STR_JSON).unwrap();
         println!("{:#?}", ari_event);
     }
+
+    // cargo test -- --show-output test_event_envelope
+    #[test]
+    fn test_event_envelope() {
+        let ari_event: AriEvent = serde_json::from_str(STR_JSON2).unwrap();
+        assert_eq!(ari_event.event_type(), "StasisStart");
+        assert_eq!(ari_event.application(), "va-voicegw-rs");
+        assert_eq!(
+            ari_event.envelope().asterisk_id.as_deref(),
+            Some("00:50:56:98:74:21")
+        );
+        assert_eq!(ari_event.channel().unwrap().id, "1610050377.0");
+        #[cfg(feature = "parse-event-datetimes")]
+        assert_eq!(
+            ari_event.timestamp().to_rfc3339(),
+            "2021-01-07T21:12:57.268+01:00"
+        );
+        #[cfg(not(feature = "parse-event-datetimes"))]
+        assert_eq!(ari_event.timestamp(), "2021-01-07T21:12:57.268+0100");
+
+        // envelope is flattened back on serialization
+        let value = serde_json::to_value(&ari_event).unwrap();
+        assert_eq!(value["type"], "StasisStart");
+        assert_eq!(value["application"], "va-voicegw-rs");
+        assert_eq!(value["timestamp"], "2021-01-07T21:12:57.268+0100");
+        assert!(value.get("envelope").is_none());
+    }
 }
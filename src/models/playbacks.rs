@@ -14,6 +14,17 @@ pub struct Playback {
     /// For media types that support multiple languages, the language requested for playback.
     pub language: String,
     /// Current state of the playback operation.
-    /// allowed values: queued, playing, continuing, done
-    pub state: String,
+    pub state: PlaybackState,
+}
+
+string_enum! {
+    /// State of the playback operation.
+    pub enum PlaybackState {
+        Queued => "queued",
+        Playing => "playing",
+        /// Playback of media list continues with the next media.
+        Continuing => "continuing",
+        Done => "done",
+        Failed => "failed",
+    }
 }
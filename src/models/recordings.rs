@@ -7,6 +7,124 @@ pub struct Recording {
     /// Format the recording was recorded in
     pub format: String,
     /// State of the recording
-    pub state: String,
+    pub state: RecordingState,
     pub target_uri: String,
 }
+
+string_enum! {
+    /// State of the recording.
+    pub enum RecordingState {
+        Queued => "queued",
+        Recording => "recording",
+        Paused => "paused",
+        Done => "done",
+        Failed => "failed",
+        Canceled => "canceled",
+    }
+}
+
+string_enum! {
+    /// Audio format of the recording. Other covers any other format supported by Asterisk
+    /// (see `core show file formats`).
+    #[derive(Default)]
+    pub enum RecordingFormat {
+        #[default]
+        Wav => "wav",
+        Wav16 => "wav16",
+        Gsm => "gsm",
+        Ulaw => "ulaw",
+        Alaw => "alaw",
+        G722 => "g722",
+        Sln => "sln",
+        Sln16 => "sln16",
+    }
+}
+
+/// DTMF key(s) terminating the recording.
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize)]
+pub enum TerminateOn {
+    #[default]
+    #[serde(rename = "none")]
+    None,
+    #[serde(rename = "any")]
+    Any,
+    #[serde(rename = "*")]
+    Star,
+    #[serde(rename = "#")]
+    Hash,
+}
+
+/// Action taken when recording with the same name already exists.
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IfExists {
+    #[default]
+    Fail,
+    Overwrite,
+    Append,
+}
+
+/// Options of [record](crate::apis::channels::ChannelsAPI::record).
+///
+/// ```
+/// use asterisk_ari_client_rs::models::recordings::{IfExists, RecordOptions, RecordingFormat, TerminateOn};
+///
+/// let options = RecordOptions::default()
+///     .name("voicemail/1001")
+///     .format(RecordingFormat::Wav16)
+///     .terminate_on(TerminateOn::Hash)
+///     .max_duration_seconds(120)
+///     .if_exists(IfExists::Overwrite)
+///     .beep(true);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct RecordOptions {
+    pub(crate) name: Option<String>,
+    pub(crate) format: RecordingFormat,
+    pub(crate) terminate_on: TerminateOn,
+    pub(crate) max_duration_seconds: usize,
+    pub(crate) max_silence_seconds: usize,
+    pub(crate) if_exists: IfExists,
+    pub(crate) beep: bool,
+}
+
+impl RecordOptions {
+    /// Recording name (relative to /var/spool/asterisk/recording), defaults to channel id.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn format(mut self, format: RecordingFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn terminate_on(mut self, terminate_on: TerminateOn) -> Self {
+        self.terminate_on = terminate_on;
+        self
+    }
+
+    /// Maximal duration of the recording, 0 (default) means no limit.
+    pub fn max_duration_seconds(mut self, seconds: usize) -> Self {
+        self.max_duration_seconds = seconds;
+        self
+    }
+
+    /// Maximal duration of silence before ending the recording, 0 (default) means no limit.
+    pub fn max_silence_seconds(mut self, seconds: usize) -> Self {
+        self.max_silence_seconds = seconds;
+        self
+    }
+
+    pub fn if_exists(mut self, if_exists: IfExists) -> Self {
+        self.if_exists = if_exists;
+        self
+    }
+
+    /// Play beep when recording begins.
+    pub fn beep(mut self, beep: bool) -> Self {
+        self.beep = beep;
+        self
+    }
+}
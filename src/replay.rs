@@ -0,0 +1,476 @@
+//! Recording of websocket events and REST calls into JSONL file (see [set_recorder](crate::client::AriClient::set_recorder))
+//! and replay of recorded events into the same handlers, e.g. to reproduce production incident locally.
+
+use crate::client::AriClient;
+use crate::errors::Result;
+use crate::models::events::AriEvent;
+use chrono::{SecondsFormat, Utc};
+use log::*;
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc;
+use tokio::sync::oneshot;
+use tokio::time::{sleep, sleep_until, Duration, Instant};
+
+/// Single line of the recording file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordedEntry {
+    /// Text message received from Asterisk websocket.
+    Event {
+        /// Milliseconds elapsed since the recorder was created.
+        elapsed_ms: u64,
+        /// Wall clock time (RFC 3339) the message was received.
+        time: String,
+        /// Message as received, stored as JSON value if it is valid JSON, otherwise as string.
+        message: serde_json::Value,
+    },
+    /// REST call made by the client (every attempt when retrying).
+    Request {
+        /// Milliseconds elapsed since the recorder was created.
+        elapsed_ms: u64,
+        /// Wall clock time (RFC 3339) the response was received.
+        time: String,
+        method: String,
+        /// Resource path relative to ARI base URL.
+        path: String,
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        body: Option<String>,
+        /// Response status, None if no response was received.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        status: Option<u16>,
+        /// Transport error if no response was received.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        error: Option<String>,
+    },
+}
+
+impl RecordedEntry {
+    pub fn elapsed(&self) -> Duration {
+        match self {
+            RecordedEntry::Event { elapsed_ms, .. } | RecordedEntry::Request { elapsed_ms, .. } => {
+                Duration::from_millis(*elapsed_ms)
+            }
+        }
+    }
+}
+
+/// Writes websocket messages and REST calls of the client to JSONL file, one [RecordedEntry] per line.
+/// Recorder can be shared by several clients (clones write to the same file).
+/// Entries are written by a background thread (flushed in batches), i.e. slow disk never blocks event processing.
+/// Writing errors are only logged, they never affect event processing.
+#[derive(Clone)]
+pub struct EventRecorder {
+    commands: mpsc::Sender<RecorderCommand>,
+    started: Instant,
+}
+
+enum RecorderCommand {
+    Write(RecordedEntry),
+    Flush(oneshot::Sender<()>),
+}
+
+impl EventRecorder {
+    /// Recorder writing into given writer. The writer is flushed whenever there are no more entries waiting
+    /// and once all clones of the recorder are dropped.
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        let (commands, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("ari-event-recorder".into())
+            .spawn(move || write_entries(writer, receiver))
+            .expect("unable to spawn event recorder thread");
+        EventRecorder {
+            commands,
+            started: Instant::now(),
+        }
+    }
+
+    /// Recorder writing into newly created (or truncated) file.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(EventRecorder::new(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Waits until all entries recorded so far are written and flushed.
+    pub async fn flush(&self) {
+        let (sender, receiver) = oneshot::channel();
+        if self.commands.send(RecorderCommand::Flush(sender)).is_ok() {
+            let _ = receiver.await;
+        }
+    }
+
+    pub(crate) fn record_event(&self, message: &str) {
+        let message = serde_json::from_str(message)
+            .unwrap_or_else(|_| serde_json::Value::String(message.to_owned()));
+        self.write(RecordedEntry::Event {
+            elapsed_ms: self.elapsed_ms(),
+            time: now(),
+            message,
+        });
+    }
+
+    pub(crate) fn record_request(
+        &self,
+        method: &Method,
+        path: &str,
+        body: Option<&str>,
+        outcome: std::result::Result<StatusCode, &reqwest::Error>,
+    ) {
+        let (status, error) = match outcome {
+            Ok(status) => (Some(status.as_u16()), None),
+            Err(err) => (None, Some(err.to_string())),
+        };
+        self.write(RecordedEntry::Request {
+            elapsed_ms: self.elapsed_ms(),
+            time: now(),
+            method: method.to_string(),
+            path: path.to_owned(),
+            body: body.map(str::to_owned),
+            status,
+            error,
+        });
+    }
+
+    fn elapsed_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    fn write(&self, entry: RecordedEntry) {
+        if self.commands.send(RecorderCommand::Write(entry)).is_err() {
+            warn!("event recorder thread is gone, entry not recorded");
+        }
+    }
+}
+
+/// body of the recorder thread, runs until all recorder clones are dropped
+fn write_entries<W: Write>(mut writer: W, commands: mpsc::Receiver<RecorderCommand>) {
+    let mut flush_waiters = vec![];
+    while let Ok(command) = commands.recv() {
+        // write everything already waiting and flush once
+        let mut next = Some(command);
+        while let Some(command) = next {
+            match command {
+                RecorderCommand::Write(entry) => {
+                    let result = serde_json::to_string(&entry)
+                        .map_err(std::io::Error::from)
+                        .and_then(|line| writeln!(writer, "{}", line));
+                    if let Err(err) = result {
+                        warn!("unable to write event recording entry {:?}", err);
+                    }
+                }
+                RecorderCommand::Flush(waiter) => flush_waiters.push(waiter),
+            }
+            next = commands.try_recv().ok();
+        }
+        if let Err(err) = writer.flush() {
+            warn!("unable to flush event recording {:?}", err);
+        }
+        for waiter in flush_waiters.drain(..) {
+            let _ = waiter.send(());
+        }
+    }
+}
+
+/// Replays events recorded by [EventRecorder] into the client, i.e. to its event bus and typed senders
+/// as if they were received from the websocket. REST calls are not replayed, they are available
+/// via [entries](EventReplayer::entries) for inspection.
+#[derive(Clone, Debug)]
+pub struct EventReplayer {
+    entries: Vec<RecordedEntry>,
+    speed: f64,
+}
+
+impl EventReplayer {
+    /// Loads recording file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        EventReplayer::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Loads recording from reader, empty lines are skipped.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut entries = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            entries.push(serde_json::from_str(&line)?);
+        }
+        Ok(EventReplayer {
+            entries,
+            speed: 1.0,
+        })
+    }
+
+    /// Replay speed, 1.0 (default) keeps original relative timing, 2.0 replays twice as fast,
+    /// f64::INFINITY replays without any delays. Delays too long to be represented never expire.
+    pub fn speed(mut self, speed: f64) -> Self {
+        assert!(speed > 0.0, "replay speed must be positive");
+        self.speed = speed;
+        self
+    }
+
+    pub fn entries(&self) -> &[RecordedEntry] {
+        &self.entries
+    }
+
+    /// Recorded events in original order. Messages which cannot be parsed are skipped.
+    pub fn events(&self) -> Vec<AriEvent> {
+        self.recorded_events().map(|(_, event)| event).collect()
+    }
+
+    /// Dispatches recorded events to the client keeping relative timing (adjusted by speed).
+    /// Timing is relative to the first event, i.e. leading idle time is skipped. Returns number of replayed events.
+    pub async fn replay(&self, client: &AriClient) -> usize {
+        let started = Instant::now();
+        let mut first_elapsed = None;
+        let mut replayed = 0;
+
+        for (elapsed, event) in self.recorded_events() {
+            // hand edited recording might not be ordered, such events are replayed immediately
+            let offset = elapsed.saturating_sub(*first_elapsed.get_or_insert(elapsed));
+            // very slow speed might scale the offset beyond what Duration or Instant can hold
+            let deadline = Duration::try_from_secs_f64(offset.as_secs_f64() / self.speed)
+                .ok()
+                .and_then(|delay| started.checked_add(delay));
+            match deadline {
+                Some(deadline) => sleep_until(deadline).await,
+                None => sleep(Duration::MAX).await,
+            }
+            trace!("replaying ari_event: {:#?}", event);
+            client.dispatch_event(event).await;
+            replayed += 1;
+        }
+        replayed
+    }
+
+    fn recorded_events(&self) -> impl Iterator<Item = (Duration, AriEvent)> + '_ {
+        self.entries.iter().filter_map(|entry| match entry {
+            RecordedEntry::Event { message, .. } => {
+                match serde_json::from_value::<AriEvent>(message.clone()) {
+                    Ok(event) => Some((entry.elapsed(), event)),
+                    Err(err) => {
+                        warn!(
+                            "skipping recorded message which is not ARI event: {:?}. Message: {}",
+                            err, message
+                        );
+                        None
+                    }
+                }
+            }
+            RecordedEntry::Request { .. } => None,
+        })
+    }
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::channels::ChannelsAPI;
+    use crate::testing::fixtures::{channel_json, event_json};
+    use crate::testing::MockAriServer;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+    use tokio::time::timeout;
+
+    /// in-memory recording target
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn stasis_start_json(channel_id: &str) -> serde_json::Value {
+        event_json(
+            "StasisStart",
+            json!({ "args": [], "channel": channel_json(channel_id) }),
+        )
+    }
+
+    fn event_line(elapsed_ms: u64, channel_id: &str) -> String {
+        let message = stasis_start_json(channel_id);
+        serde_json::to_string(&RecordedEntry::Event {
+            elapsed_ms,
+            time: now(),
+            message,
+        })
+        .unwrap()
+    }
+
+    // cargo test -- --show-output test_event_recorder
+    #[tokio::test]
+    async fn test_event_recorder() {
+        let server = MockAriServer::start().await;
+        server.respond(
+            Method::POST,
+            "/channels/1610050377.0/answer",
+            StatusCode::NO_CONTENT,
+            "",
+        );
+        let buffer = SharedBuffer::default();
+        let mut client = server.client();
+        let recorder = EventRecorder::new(buffer.clone());
+        client.set_recorder(Some(recorder.clone()));
+
+        let loop_client = client.clone();
+        let processing_loop =
+            tokio::spawn(
+                async move { loop_client.ari_processing_loop(vec!["my-app".into()]).await },
+            );
+        server.wait_for_websocket(1).await;
+
+        let mut events = client.subscribe_events();
+        // both messages are recorded once StasisStart is received
+        server.send_raw_event("not an event");
+        server.send_raw_event(stasis_start_json("1610050377.0").to_string());
+        timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        client.answer("1610050377.0").await.unwrap();
+
+        client.shutdown_handle().shutdown();
+        processing_loop.await.unwrap().unwrap();
+        recorder.flush().await;
+
+        let recording = buffer.0.lock().unwrap().clone();
+        let replayer = EventReplayer::from_reader(recording.as_slice()).unwrap();
+        assert_eq!(replayer.entries().len(), 3);
+        match &replayer.entries()[0] {
+            RecordedEntry::Event { message, .. } => assert_eq!(message, "not an event"),
+            entry => panic!("unexpected entry {:?}", entry),
+        }
+        match &replayer.entries()[2] {
+            RecordedEntry::Request {
+                method,
+                path,
+                status,
+                error,
+                ..
+            } => {
+                assert_eq!(method, "POST");
+                assert_eq!(path, "/channels/1610050377.0/answer");
+                assert_eq!(*status, Some(204));
+                assert_eq!(*error, None);
+            }
+            entry => panic!("unexpected entry {:?}", entry),
+        }
+
+        // invalid message is skipped
+        let replay_client =
+            AriClient::new("http://localhost:8088/ari".into(), "".into(), "".into());
+        let mut events = replay_client.subscribe_events();
+        let replayer = replayer.speed(f64::INFINITY);
+        assert_eq!(replayer.events().len(), 1);
+        assert_eq!(replayer.replay(&replay_client).await, 1);
+        match events.try_recv().unwrap() {
+            AriEvent::StasisStart(event) => assert_eq!(event.channel.id, "1610050377.0"),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    // cargo test -- --show-output test_event_replayer_timing
+    #[tokio::test(start_paused = true)]
+    async fn test_event_replayer_timing() {
+        let recording = [
+            event_line(1000, "first"),
+            "".into(),
+            event_line(1500, "second"),
+            event_line(3000, "third"),
+        ]
+        .join("\n");
+        let replayer = EventReplayer::from_reader(recording.as_bytes())
+            .unwrap()
+            .speed(2.0);
+
+        let client = AriClient::new("http://localhost:8088/ari".into(), "".into(), "".into());
+        let mut events = client.subscribe_events();
+        let started = Instant::now();
+        let replay = tokio::spawn({
+            let client = client.clone();
+            async move { replayer.replay(&client).await }
+        });
+
+        // leading second is skipped, the rest is replayed twice as fast
+        for (channel_id, offset) in [("first", 0), ("second", 250), ("third", 1000)] {
+            match events.recv().await.unwrap() {
+                AriEvent::StasisStart(event) => assert_eq!(event.channel.id, channel_id),
+                event => panic!("unexpected event {:?}", event),
+            }
+            assert_eq!(started.elapsed(), Duration::from_millis(offset));
+        }
+        assert_eq!(replay.await.unwrap(), 3);
+    }
+
+    // cargo test -- --show-output test_event_replayer_unordered
+    #[tokio::test(start_paused = true)]
+    async fn test_event_replayer_unordered() {
+        let recording = [
+            event_line(1000, "first"),
+            event_line(500, "earlier"),
+            event_line(2000, "second"),
+            event_line(1800, "late"),
+        ]
+        .join("\n");
+        let replayer = EventReplayer::from_reader(recording.as_bytes()).unwrap();
+
+        let client = AriClient::new("http://localhost:8088/ari".into(), "".into(), "".into());
+        let mut events = client.subscribe_events();
+        let started = Instant::now();
+        let replay = tokio::spawn({
+            let client = client.clone();
+            async move { replayer.replay(&client).await }
+        });
+
+        // events recorded out of order are replayed immediately after their predecessor
+        for (channel_id, offset) in [
+            ("first", 0),
+            ("earlier", 0),
+            ("second", 1000),
+            ("late", 1000),
+        ] {
+            match events.recv().await.unwrap() {
+                AriEvent::StasisStart(event) => assert_eq!(event.channel.id, channel_id),
+                event => panic!("unexpected event {:?}", event),
+            }
+            assert_eq!(started.elapsed(), Duration::from_millis(offset));
+        }
+        assert_eq!(replay.await.unwrap(), 4);
+    }
+
+    // cargo test -- --show-output test_event_replayer_tiny_speed
+    #[tokio::test(start_paused = true)]
+    async fn test_event_replayer_tiny_speed() {
+        let recording = [event_line(1000, "first"), event_line(2000, "second")].join("\n");
+        let replayer = EventReplayer::from_reader(recording.as_bytes())
+            .unwrap()
+            .speed(f64::MIN_POSITIVE);
+
+        let client = AriClient::new("http://localhost:8088/ari".into(), "".into(), "".into());
+        let mut events = client.subscribe_events();
+        let replay = tokio::spawn({
+            let client = client.clone();
+            async move { replayer.replay(&client).await }
+        });
+
+        // delay of the second event overflows, replay must wait instead of panicking
+        assert!(events.recv().await.is_ok());
+        sleep(Duration::from_secs(3600)).await;
+        assert!(events.try_recv().is_err());
+        assert!(!replay.is_finished());
+        replay.abort();
+    }
+}
@@ -0,0 +1,118 @@
+//! Retry policy for REST calls, see [set_retry_policy](crate::client::AriClient::set_retry_policy).
+
+use crate::connection::exponential_delay;
+use reqwest::{Method, StatusCode};
+use std::time::Duration;
+
+/// Function deciding whether request with given method and path (relative to ARI base URL, e.g. `/playbacks/{id}`)
+/// is safe to repeat.
+pub type RetryPredicate = fn(&Method, &str) -> bool;
+
+/// Default [RetryPredicate]: GET (fetching resources) and stopping of a playback (`DELETE /playbacks/{id}`),
+/// repeating them has no side effect. Other DELETE requests are not retried, e.g. hangup or deletion
+/// of a recording may have taken effect even though the request failed.
+pub fn default_retry_predicate(method: &Method, path: &str) -> bool {
+    match *method {
+        Method::GET => true,
+        Method::DELETE => matches!(
+            path.strip_prefix("/playbacks/"),
+            Some(playback_id) if !playback_id.is_empty() && !playback_id.contains('/')
+        ),
+        _ => false,
+    }
+}
+
+/// Opt-in retry of REST calls failing with transient errors (e.g. during Asterisk restart).
+/// Only requests accepted by [retry_predicate](Self::retry_predicate) are retried, see [default_retry_predicate].
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Maximal number of attempts including the first one.
+    pub max_attempts: u32,
+
+    /// Delay before the first retry.
+    pub initial_backoff: Duration,
+
+    /// Upper bound of the delay between retries.
+    pub max_backoff: Duration,
+
+    /// Factor the delay is multiplied by after each retry, values below 1.0 are treated as 1.0.
+    pub multiplier: f64,
+
+    /// Requests (method and path) which are retried.
+    pub retry_predicate: RetryPredicate,
+
+    /// Response status codes which are retried.
+    pub retry_status_codes: Vec<StatusCode>,
+
+    /// Retry when connection cannot be established, is reset or request times out.
+    pub retry_connection_errors: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+            retry_predicate: default_retry_predicate,
+            retry_status_codes: vec![
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            retry_connection_errors: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before given retry (counted from 1).
+    pub fn backoff(&self, retry: u32) -> Duration {
+        exponential_delay(
+            self.initial_backoff,
+            self.multiplier,
+            self.max_backoff,
+            retry,
+        )
+    }
+
+    /// Whether request with given method and path that failed in given attempt (counted from 1) can be repeated.
+    pub fn allows_retry(&self, method: &Method, path: &str, attempt: u32) -> bool {
+        attempt < self.max_attempts && (self.retry_predicate)(method, path)
+    }
+
+    pub fn is_retryable_status(&self, status: StatusCode) -> bool {
+        self.retry_status_codes.contains(&status)
+    }
+
+    pub fn is_retryable_error(&self, err: &reqwest::Error) -> bool {
+        self.retry_connection_errors && (err.is_connect() || err.is_timeout() || err.is_request())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // cargo test -- --show-output test_retry_policy
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::default();
+        assert!(policy.allows_retry(&Method::GET, "/channels", 1));
+        assert!(policy.allows_retry(&Method::GET, "/channels", 2));
+        assert!(!policy.allows_retry(&Method::GET, "/channels", 3));
+        assert!(policy.allows_retry(&Method::DELETE, "/playbacks/7b4ed6c5", 1));
+        // hangup and recording deletion must not be repeated
+        assert!(!policy.allows_retry(&Method::DELETE, "/channels/1607454635.2", 1));
+        assert!(!policy.allows_retry(&Method::DELETE, "/recordings/stored/greeting", 1));
+        assert!(!policy.allows_retry(&Method::DELETE, "/playbacks/", 1));
+        assert!(!policy.allows_retry(&Method::POST, "/channels/1607454635.2/answer", 1));
+        assert!(policy.is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!policy.is_retryable_status(StatusCode::NOT_FOUND));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(10), Duration::from_secs(5));
+    }
+}
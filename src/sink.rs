@@ -0,0 +1,503 @@
+//! Non-blocking delivery of events dispatched by [ari_processing_loop](crate::client::AriClient::ari_processing_loop).
+//! Every subscriber has its own queue with [BackpressurePolicy] deciding what happens when the subscriber
+//! cannot keep up, i.e. one slow consumer does not stall websocket processing (including ping handling)
+//! unless [Block](BackpressurePolicy::Block) is chosen explicitly.
+
+use crate::models::events::AriEvent;
+use async_trait::async_trait;
+use log::*;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc::Sender, Notify};
+use tokio::task::JoinHandle;
+
+/// What to do with new event when subscriber's queue is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// Wait until subscriber makes room in the queue, i.e. websocket processing is suspended meanwhile.
+    Block,
+    /// Discard the oldest queued event to make room for the new one.
+    DropOldest,
+    /// Discard the new event.
+    DropNewest,
+    /// Never discard events, warn once the number of queued events reaches the threshold.
+    Unbounded { warn_threshold: usize },
+}
+
+impl Default for BackpressurePolicy {
+    fn default() -> Self {
+        BackpressurePolicy::Unbounded {
+            warn_threshold: 1000,
+        }
+    }
+}
+
+/// Delivery metrics of single subscriber.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DeliveryStats {
+    /// Events put into the queue.
+    pub delivered: u64,
+    /// Events discarded because of full queue.
+    pub dropped: u64,
+    /// Events currently waiting in the queue.
+    pub queued: usize,
+    /// Highest number of events waiting in the queue so far.
+    pub max_queued: usize,
+}
+
+impl std::ops::Add for DeliveryStats {
+    type Output = DeliveryStats;
+
+    fn add(self, other: DeliveryStats) -> DeliveryStats {
+        DeliveryStats {
+            delivered: self.delivered + other.delivered,
+            dropped: self.dropped + other.dropped,
+            queued: self.queued + other.queued,
+            max_queued: self.max_queued.max(other.max_queued),
+        }
+    }
+}
+
+/// Destination of events dispatched by ari_processing_loop,
+/// see [add_event_sink](crate::client::AriClient::add_event_sink).
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    /// Called from websocket loop for every received event, should return as soon as possible.
+    async fn deliver(&self, event: &AriEvent);
+
+    /// Closed sinks are removed from the client and receive no more events.
+    fn is_closed(&self) -> bool {
+        false
+    }
+
+    /// Called once ari_processing_loop is finished, should wait until events delivered so far
+    /// are handed over to the consumer (e.g. by background tasks of the sink).
+    async fn flush(&self) {}
+}
+
+/// Queue with given capacity (ignored by [Unbounded](BackpressurePolicy::Unbounded) policy) and backpressure policy.
+pub fn event_queue<T>(
+    capacity: usize,
+    policy: BackpressurePolicy,
+) -> (EventQueueSender<T>, EventQueueReceiver<T>) {
+    assert!(capacity > 0, "event queue capacity must be positive");
+    let shared = Arc::new(Shared {
+        state: Mutex::new(QueueState {
+            items: VecDeque::new(),
+            stats: DeliveryStats::default(),
+            overflowing: false,
+            sender_closed: false,
+            receiver_closed: false,
+        }),
+        item_available: Notify::new(),
+        space_available: Notify::new(),
+        capacity,
+        policy,
+    });
+    (
+        EventQueueSender {
+            shared: shared.clone(),
+        },
+        EventQueueReceiver { shared },
+    )
+}
+
+struct Shared<T> {
+    state: Mutex<QueueState<T>>,
+    item_available: Notify,
+    space_available: Notify,
+    capacity: usize,
+    policy: BackpressurePolicy,
+}
+
+struct QueueState<T> {
+    items: VecDeque<T>,
+    stats: DeliveryStats,
+    // set when capacity (or warning threshold) is reached, used to warn once per overflow
+    overflowing: bool,
+    sender_closed: bool,
+    receiver_closed: bool,
+}
+
+impl<T> QueueState<T> {
+    fn enqueue(&mut self, item: T) {
+        self.items.push_back(item);
+        self.stats.delivered += 1;
+        self.stats.queued = self.items.len();
+        self.stats.max_queued = self.stats.max_queued.max(self.items.len());
+    }
+
+    /// returns true for the first overflow after queue had room again
+    fn overflow(&mut self) -> bool {
+        !std::mem::replace(&mut self.overflowing, true)
+    }
+}
+
+/// Producing side of [event_queue].
+pub struct EventQueueSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> EventQueueSender<T> {
+    /// Puts item into the queue according to the policy. Waits only with [Block](BackpressurePolicy::Block) policy.
+    /// Returns false if the receiver was dropped.
+    pub async fn push(&self, item: T) -> bool {
+        let shared = &self.shared;
+        let mut item = Some(item);
+        loop {
+            {
+                let mut state = shared.state.lock().unwrap();
+                if state.receiver_closed {
+                    return false;
+                }
+                let full = state.items.len() >= shared.capacity;
+                match shared.policy {
+                    BackpressurePolicy::Unbounded { warn_threshold } => {
+                        if state.items.len() + 1 >= warn_threshold && state.overflow() {
+                            warn!(
+                                "event queue reached {} queued events, subscriber is too slow",
+                                state.items.len() + 1
+                            );
+                        }
+                    }
+                    _ if !full => {}
+                    BackpressurePolicy::DropNewest => {
+                        state.stats.dropped += 1;
+                        if state.overflow() {
+                            warn!("event queue full, dropping newest events");
+                        }
+                        return true;
+                    }
+                    BackpressurePolicy::DropOldest => {
+                        state.items.pop_front();
+                        state.stats.dropped += 1;
+                        if state.overflow() {
+                            warn!("event queue full, dropping oldest events");
+                        }
+                    }
+                    BackpressurePolicy::Block => {
+                        if state.overflow() {
+                            warn!("event queue full, waiting for subscriber");
+                        }
+                    }
+                }
+                if !full || shared.policy != BackpressurePolicy::Block {
+                    state.enqueue(item.take().unwrap());
+                    shared.item_available.notify_one();
+                    return true;
+                }
+            }
+            shared.space_available.notified().await;
+        }
+    }
+
+    pub fn stats(&self) -> DeliveryStats {
+        self.shared.state.lock().unwrap().stats
+    }
+
+    /// True if the receiver was dropped.
+    pub fn is_closed(&self) -> bool {
+        self.shared.state.lock().unwrap().receiver_closed
+    }
+}
+
+impl<T> Drop for EventQueueSender<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().sender_closed = true;
+        self.shared.item_available.notify_one();
+    }
+}
+
+#[async_trait]
+impl EventSink for EventQueueSender<AriEvent> {
+    async fn deliver(&self, event: &AriEvent) {
+        self.push(event.clone()).await;
+    }
+
+    fn is_closed(&self) -> bool {
+        EventQueueSender::is_closed(self)
+    }
+}
+
+/// Consuming side of [event_queue].
+pub struct EventQueueReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> EventQueueReceiver<T> {
+    /// Next queued item, None once the sender is dropped and the queue is empty.
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if let Some(item) = self.pop(&mut state) {
+                    return Some(item);
+                }
+                if state.sender_closed {
+                    return None;
+                }
+            }
+            self.shared.item_available.notified().await;
+        }
+    }
+
+    /// Next queued item if there is any.
+    pub fn try_recv(&mut self) -> Option<T> {
+        let mut state = self.shared.state.lock().unwrap();
+        self.pop(&mut state)
+    }
+
+    pub fn stats(&self) -> DeliveryStats {
+        self.shared.state.lock().unwrap().stats
+    }
+
+    /// Closes the queue (sender's push returns false from now on) unless there are items waiting.
+    pub(crate) fn close_if_empty(&mut self) -> bool {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.items.is_empty() {
+            state.receiver_closed = true;
+        }
+        state.receiver_closed
+    }
+
+    fn pop(&self, state: &mut QueueState<T>) -> Option<T> {
+        let item = state.items.pop_front()?;
+        state.stats.queued = state.items.len();
+        let threshold = match self.shared.policy {
+            BackpressurePolicy::Unbounded { warn_threshold } => warn_threshold,
+            _ => self.shared.capacity,
+        };
+        if state.items.len() < threshold {
+            state.overflowing = false;
+        }
+        self.shared.space_available.notify_one();
+        Some(item)
+    }
+}
+
+impl<T> Drop for EventQueueReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().receiver_closed = true;
+        self.shared.space_available.notify_one();
+    }
+}
+
+/// Sender set via set_*_sender methods of [AriClient](crate::client::AriClient). Unless [Block](BackpressurePolicy::Block)
+/// policy is used events are forwarded to it via queue created (together with forwarding task) on the first delivery.
+pub(crate) struct TypedSender<T> {
+    sender: Sender<T>,
+    forwarder: Arc<Mutex<Option<Forwarder<T>>>>,
+    // metrics of flushed forwarders
+    stats: Arc<Mutex<DeliveryStats>>,
+}
+
+/// Queue of [TypedSender] and the task forwarding queued events to the sender.
+struct Forwarder<T> {
+    queue: Arc<EventQueueSender<T>>,
+    task: JoinHandle<()>,
+}
+
+impl<T> Clone for TypedSender<T> {
+    fn clone(&self) -> Self {
+        TypedSender {
+            sender: self.sender.clone(),
+            forwarder: self.forwarder.clone(),
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> TypedSender<T> {
+    pub(crate) fn new(sender: Sender<T>) -> Self {
+        TypedSender {
+            sender,
+            forwarder: Arc::new(Mutex::new(None)),
+            stats: Arc::new(Mutex::new(DeliveryStats::default())),
+        }
+    }
+
+    pub(crate) async fn deliver(&self, event: T, policy: BackpressurePolicy, event_type: &str) {
+        if policy == BackpressurePolicy::Block {
+            if let Err(send_err) = self.sender.send(event).await {
+                error!(
+                    "ari_processing_loop {} sending error {:?}: ",
+                    event_type, send_err
+                );
+            }
+            return;
+        }
+
+        let queue = {
+            let mut forwarder = self.forwarder.lock().unwrap();
+            let forwarder =
+                forwarder.get_or_insert_with(|| self.spawn_forwarder(policy, event_type));
+            forwarder.queue.clone()
+        };
+        queue.push(event).await;
+    }
+
+    fn spawn_forwarder(&self, policy: BackpressurePolicy, event_type: &str) -> Forwarder<T> {
+        let (queue, mut receiver) = event_queue(self.sender.max_capacity(), policy);
+        let sender = self.sender.clone();
+        let event_type = event_type.to_owned();
+        let task = tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                if let Err(send_err) = sender.send(event).await {
+                    error!(
+                        "ari_processing_loop {} sending error {:?}: ",
+                        event_type, send_err
+                    );
+                    break;
+                }
+            }
+        });
+        Forwarder {
+            queue: Arc::new(queue),
+            task,
+        }
+    }
+
+    /// Waits until all queued events are sent, i.e. until the receiver of the sender takes them
+    /// (or is dropped). Forwarding task is finished, next delivery starts a new one.
+    pub(crate) async fn flush(&self) {
+        let forwarder = self.forwarder.lock().unwrap().take();
+        if let Some(Forwarder { queue, task }) = forwarder {
+            // closing the queue ends the task once queued events are sent
+            let queue_stats = queue.stats();
+            drop(queue);
+            if let Err(join_err) = task.await {
+                error!("ari_processing_loop event forwarding failed {:?}", join_err);
+            }
+            let mut stats = self.stats.lock().unwrap();
+            *stats = *stats
+                + DeliveryStats {
+                    queued: 0,
+                    ..queue_stats
+                };
+        }
+    }
+
+    pub(crate) fn stats(&self) -> DeliveryStats {
+        let stats = *self.stats.lock().unwrap();
+        self.forwarder
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(stats, |forwarder| stats + forwarder.queue.stats())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::AriClient;
+    use crate::testing::fixtures::stasis_start;
+    use tokio::sync::mpsc;
+    use tokio::time::{timeout, Duration};
+
+    async fn push_all(sender: &EventQueueSender<u32>, items: impl IntoIterator<Item = u32>) {
+        for item in items {
+            assert!(sender.push(item).await);
+        }
+    }
+
+    fn drain(receiver: &mut EventQueueReceiver<u32>) -> Vec<u32> {
+        std::iter::from_fn(|| receiver.try_recv()).collect()
+    }
+
+    // cargo test -- --show-output test_event_queue_policies
+    #[tokio::test]
+    async fn test_event_queue_policies() {
+        let (sender, mut receiver) = event_queue(3, BackpressurePolicy::DropOldest);
+        push_all(&sender, 1..=5).await;
+        assert_eq!(drain(&mut receiver), vec![3, 4, 5]);
+        assert_eq!(receiver.stats().dropped, 2);
+        assert_eq!(receiver.stats().delivered, 5);
+
+        let (sender, mut receiver) = event_queue(3, BackpressurePolicy::DropNewest);
+        push_all(&sender, 1..=5).await;
+        assert_eq!(drain(&mut receiver), vec![1, 2, 3]);
+        assert_eq!(sender.stats().dropped, 2);
+        assert_eq!(sender.stats().delivered, 3);
+
+        let (sender, mut receiver) =
+            event_queue(3, BackpressurePolicy::Unbounded { warn_threshold: 2 });
+        push_all(&sender, 1..=5).await;
+        assert_eq!(
+            receiver.stats(),
+            DeliveryStats {
+                delivered: 5,
+                dropped: 0,
+                queued: 5,
+                max_queued: 5,
+            }
+        );
+        assert_eq!(drain(&mut receiver), vec![1, 2, 3, 4, 5]);
+        assert_eq!(receiver.stats().queued, 0);
+
+        // closed receiver is reported to the sender
+        drop(receiver);
+        assert!(sender.is_closed());
+        assert!(!sender.push(6).await);
+    }
+
+    // cargo test -- --show-output test_event_queue_block
+    #[tokio::test]
+    async fn test_event_queue_block() {
+        let (sender, mut receiver) = event_queue(2, BackpressurePolicy::Block);
+        push_all(&sender, 1..=2).await;
+
+        let producer = tokio::spawn(async move {
+            push_all(&sender, 3..=4).await;
+        });
+        // producer is blocked until consumer makes room
+        tokio::task::yield_now().await;
+        assert_eq!(receiver.stats().queued, 2);
+
+        let mut received = vec![];
+        while let Some(item) = timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .unwrap()
+        {
+            received.push(item);
+        }
+        producer.await.unwrap();
+        assert_eq!(received, vec![1, 2, 3, 4]);
+        assert_eq!(receiver.stats().dropped, 0);
+    }
+
+    // cargo test -- --show-output test_slow_subscribers
+    #[tokio::test]
+    async fn test_slow_subscribers() {
+        let mut client = AriClient::new("http://localhost:8088/ari".into(), "".into(), "".into());
+        // typed sender which is never read
+        let (tx_stasis_start, _rx_stasis_start) = mpsc::channel(1);
+        client.set_stasis_start_sender(Some(tx_stasis_start));
+        client.set_sender_policy(BackpressurePolicy::DropNewest);
+        let mut subscriber = client.subscribe_events_with_policy(2, BackpressurePolicy::DropOldest);
+        let dropped_subscriber = client.subscribe_events_with_policy(2, BackpressurePolicy::Block);
+        drop(dropped_subscriber);
+
+        // dispatching never waits for slow (or dropped) subscribers
+        timeout(Duration::from_secs(5), async {
+            for channel_id in ["1", "2", "3", "4", "5"] {
+                client.dispatch_event(stasis_start(channel_id)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let mut received = vec![];
+        while let Some(AriEvent::StasisStart(event)) = subscriber.try_recv() {
+            received.push(event.channel.id);
+        }
+        assert_eq!(received, vec!["4", "5"]);
+        assert_eq!(subscriber.stats().dropped, 3);
+
+        // first event is taken by forwarding task and waits in the channel, the second one in the queue
+        tokio::task::yield_now().await;
+        let stats = client.sender_stats();
+        assert_eq!(stats.delivered + stats.dropped, 5);
+        assert!(stats.dropped >= 2);
+    }
+}
@@ -0,0 +1,411 @@
+//! Client-side cache of channels and bridges kept up to date from events, see
+//! [set_state_cache](crate::client::AriClient::set_state_cache). Answers questions like current channel state,
+//! caller id or which bridge the channel is in without REST round trip to Asterisk.
+
+use crate::apis::Ari;
+use crate::errors::Result;
+use crate::models::bridges::Bridge;
+use crate::models::channels::Channel;
+use crate::models::events::AriEvent;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Channels in Stasis application(s) of the client and bridges reported by Asterisk.
+/// Cheap to clone, clones share the same state.
+#[derive(Clone, Debug, Default)]
+pub struct StateCache {
+    inner: Arc<RwLock<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    channels: HashMap<String, Channel>,
+    bridges: HashMap<String, Bridge>,
+}
+
+impl StateCache {
+    pub fn new() -> Self {
+        StateCache::default()
+    }
+
+    /// Last known snapshot of the channel.
+    pub fn channel(&self, channel_id: &str) -> Option<Channel> {
+        self.inner.read().unwrap().channels.get(channel_id).cloned()
+    }
+
+    /// Channels currently in Stasis application.
+    pub fn active_calls(&self) -> Vec<Channel> {
+        self.inner
+            .read()
+            .unwrap()
+            .channels
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    pub fn bridge(&self, bridge_id: &str) -> Option<Bridge> {
+        self.inner.read().unwrap().bridges.get(bridge_id).cloned()
+    }
+
+    pub fn bridges(&self) -> Vec<Bridge> {
+        self.inner
+            .read()
+            .unwrap()
+            .bridges
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Channels in the bridge. Only channels in Stasis application are returned,
+    /// ids of all channels are available in [Bridge::channels].
+    pub fn channels_in_bridge(&self, bridge_id: &str) -> Vec<Channel> {
+        let state = self.inner.read().unwrap();
+        state
+            .bridges
+            .get(bridge_id)
+            .map(|bridge| {
+                bridge
+                    .channels
+                    .iter()
+                    .filter_map(|channel_id| state.channels.get(channel_id).cloned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Bridge the channel is in.
+    pub fn channel_bridge(&self, channel_id: &str) -> Option<Bridge> {
+        self.inner
+            .read()
+            .unwrap()
+            .bridges
+            .values()
+            .find(|bridge| bridge.channels.iter().any(|id| id == channel_id))
+            .cloned()
+    }
+
+    /// Update the state from the event. Invoked by the client for every received event
+    /// before it is delivered to subscribers.
+    pub fn apply(&self, event: &AriEvent) {
+        let mut state = self.inner.write().unwrap();
+        match event {
+            AriEvent::StasisStart(event) => {
+                state
+                    .channels
+                    .insert(event.channel.id.clone(), event.channel.clone());
+            }
+            AriEvent::StasisEnd(event) => {
+                state.channels.remove(&event.channel.id);
+            }
+            AriEvent::ChannelDestroyed(event) => {
+                state.channels.remove(&event.channel.id);
+                for bridge in state.bridges.values_mut() {
+                    bridge.channels.retain(|id| id != &event.channel.id);
+                }
+            }
+            AriEvent::ChannelVarset(event) => {
+                state.update_channel(&event.channel);
+                if let Some(channel) = state.channels.get_mut(&event.channel.id) {
+                    channel
+                        .channelvars
+                        .get_or_insert_with(HashMap::new)
+                        .insert(event.variable.clone(), event.value.clone());
+                }
+            }
+            AriEvent::BridgeCreated(event) => {
+                state
+                    .bridges
+                    .insert(event.bridge.id.clone(), event.bridge.clone());
+            }
+            AriEvent::BridgeDestroyed(event) => {
+                state.bridges.remove(&event.bridge.id);
+            }
+            AriEvent::ChannelEnteredBridge(event) => {
+                state
+                    .bridges
+                    .insert(event.bridge.id.clone(), event.bridge.clone());
+                state.update_channel(&event.channel);
+            }
+            AriEvent::ChannelLeftBridge(event) => {
+                state
+                    .bridges
+                    .insert(event.bridge.id.clone(), event.bridge.clone());
+                state.update_channel(&event.channel);
+            }
+            // any other event carries fresh snapshot of the channel (state, caller id, ...)
+            event => {
+                if let Some(channel) = event.channel() {
+                    state.update_channel(channel);
+                }
+            }
+        }
+    }
+
+    /// Replace cached state with channels in given Stasis applications and bridges fetched from Asterisk.
+    /// Channels of other applications running on the same Asterisk are skipped.
+    /// Invoked by the client (with applications it is subscribed to) after the websocket was reconnected
+    /// (i.e. events might have been missed), can be called at startup to pick up calls handled before application restart.
+    pub async fn resync<A: Ari + ?Sized>(&self, ari: &A, apps: &[String]) -> Result<()> {
+        let channels = ari.list_channels().await?;
+        let bridges = ari.list_bridges().await?;
+
+        let mut state = self.inner.write().unwrap();
+        let previous = std::mem::take(&mut state.channels);
+        state.channels = channels
+            .into_iter()
+            .filter(|channel| in_stasis_app(channel, apps))
+            .map(|mut channel| {
+                // keep variables learned from ChannelVarset events
+                if let Some(known) = previous.get(&channel.id) {
+                    merge_channelvars(&mut channel, known);
+                }
+                (channel.id.clone(), channel)
+            })
+            .collect();
+        state.bridges = bridges
+            .into_iter()
+            .map(|bridge| (bridge.id.clone(), bridge))
+            .collect();
+        Ok(())
+    }
+}
+
+impl State {
+    /// replaces snapshot of channel in Stasis, channels not in Stasis are ignored
+    fn update_channel(&mut self, channel: &Channel) {
+        if let Some(known) = self.channels.get_mut(&channel.id) {
+            let mut channel = channel.clone();
+            merge_channelvars(&mut channel, known);
+            *known = channel;
+        }
+    }
+}
+
+/// whether the channel is in one of the Stasis applications, dialplan app_data is "<app>[,<args>]"
+fn in_stasis_app(channel: &Channel, apps: &[String]) -> bool {
+    let dialplan = &channel.dialplan;
+    let app = dialplan
+        .app_data
+        .split(',')
+        .next()
+        .unwrap_or_default()
+        .trim();
+    dialplan.app_name.eq_ignore_ascii_case("Stasis") && apps.iter().any(|name| name == app)
+}
+
+/// variables known from previous snapshot which are missing in the new one
+fn merge_channelvars(channel: &mut Channel, previous: &Channel) {
+    if let Some(previous_vars) = &previous.channelvars {
+        let vars = channel.channelvars.get_or_insert_with(HashMap::new);
+        for (name, value) in previous_vars {
+            vars.entry(name.clone()).or_insert_with(|| value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::ReconnectPolicy;
+    use crate::models::channels::ChannelState;
+    use crate::testing::fixtures::{self, event};
+    use crate::testing::{AsteriskSimulator, MockAriServer};
+    use reqwest::{Method, StatusCode};
+    use serde_json::{json, Value};
+    use tokio::time::{sleep, Duration};
+
+    fn channel_json(channel_id: &str, state: &str, app_name: &str) -> Value {
+        let mut channel = fixtures::channel_json(channel_id);
+        channel["state"] = state.into();
+        channel["dialplan"]["app_name"] = app_name.into();
+        channel
+    }
+
+    fn bridge_json(bridge_id: &str, channels: &[&str]) -> Value {
+        json!({
+            "id": bridge_id,
+            "technology": "simple_bridge",
+            "bridge_type": "mixing",
+            "bridge_class": "stasis",
+            "creator": "Stasis",
+            "name": "",
+            "channels": channels,
+            "creationtime": "2021-01-07T21:06:18.000+0100"
+        })
+    }
+
+    // cargo test -- --show-output test_state_cache_events
+    #[test]
+    fn test_state_cache_events() {
+        let cache = StateCache::new();
+        cache.apply(&event(
+            "StasisStart",
+            json!({ "args": [], "channel": channel_json("a", "Ring", "Stasis") }),
+        ));
+        cache.apply(&event(
+            "StasisStart",
+            json!({ "args": [], "channel": channel_json("b", "Up", "Stasis") }),
+        ));
+        cache.apply(&event(
+            "ChannelStateChange",
+            json!({ "channel": channel_json("a", "Up", "Stasis") }),
+        ));
+        cache.apply(&event(
+            "ChannelVarset",
+            json!({ "channel": channel_json("a", "Up", "Stasis"), "variable": "LANG", "value": "en-US" }),
+        ));
+        // channel not in Stasis is not cached
+        cache.apply(&event(
+            "ChannelStateChange",
+            json!({ "channel": channel_json("x", "Up", "Dial") }),
+        ));
+
+        let channel = cache.channel("a").unwrap();
+        assert_eq!(channel.state, ChannelState::Up);
+        assert_eq!(channel.channel_var("LANG"), Some("en-US"));
+        assert_eq!(channel.caller.number, "6001");
+        assert!(cache.channel("x").is_none());
+        assert_eq!(cache.active_calls().len(), 2);
+
+        cache.apply(&event(
+            "BridgeCreated",
+            json!({ "bridge": bridge_json("br", &[]) }),
+        ));
+        cache.apply(&event(
+            "ChannelEnteredBridge",
+            json!({ "bridge": bridge_json("br", &["a"]), "channel": channel_json("a", "Up", "Stasis") }),
+        ));
+        cache.apply(&event(
+            "ChannelEnteredBridge",
+            json!({ "bridge": bridge_json("br", &["a", "b"]), "channel": channel_json("b", "Up", "Stasis") }),
+        ));
+        let mut in_bridge: Vec<String> = cache
+            .channels_in_bridge("br")
+            .into_iter()
+            .map(|channel| channel.id)
+            .collect();
+        in_bridge.sort();
+        assert_eq!(in_bridge, vec!["a", "b"]);
+        assert_eq!(cache.channel_bridge("b").unwrap().id, "br");
+        // variable survives channel snapshot update
+        assert_eq!(
+            cache.channel("a").unwrap().channel_var("LANG"),
+            Some("en-US")
+        );
+
+        cache.apply(&event(
+            "ChannelLeftBridge",
+            json!({ "bridge": bridge_json("br", &["b"]), "channel": channel_json("a", "Up", "Stasis") }),
+        ));
+        assert!(cache.channel_bridge("a").is_none());
+        cache.apply(&event(
+            "ChannelDestroyed",
+            json!({ "cause": 16, "cause_txt": "Normal Clearing", "channel": channel_json("b", "Up", "Stasis") }),
+        ));
+        assert!(cache.channel("b").is_none());
+        assert!(cache.channels_in_bridge("br").is_empty());
+        cache.apply(&event(
+            "StasisEnd",
+            json!({ "channel": channel_json("a", "Up", "Stasis") }),
+        ));
+        assert!(cache.active_calls().is_empty());
+        cache.apply(&event(
+            "BridgeDestroyed",
+            json!({ "bridge": bridge_json("br", &[]) }),
+        ));
+        assert!(cache.bridges().is_empty());
+    }
+
+    // cargo test -- --show-output test_state_cache_resync
+    #[tokio::test]
+    async fn test_state_cache_resync() {
+        let server = MockAriServer::start().await;
+        let mut other_app = channel_json("d", "Up", "Stasis");
+        other_app["dialplan"]["app_data"] = "other-app,my-app".into();
+        server.respond_json(
+            Method::GET,
+            "/channels",
+            StatusCode::OK,
+            &json!([
+                channel_json("a", "Up", "Stasis"),
+                channel_json("c", "Up", "Dial"),
+                other_app
+            ]),
+        );
+        server.respond_json(
+            Method::GET,
+            "/bridges",
+            StatusCode::OK,
+            &json!([bridge_json("br", &["a", "c"])]),
+        );
+        let client = server.client();
+
+        let cache = StateCache::new();
+        cache.apply(&event(
+            "StasisStart",
+            json!({ "args": [], "channel": channel_json("gone", "Up", "Stasis") }),
+        ));
+        cache.resync(&client, &["my-app".into()]).await.unwrap();
+
+        assert!(cache.channel("gone").is_none());
+        assert!(cache.channel("c").is_none());
+        assert!(cache.channel("d").is_none());
+        assert_eq!(cache.active_calls().len(), 1);
+        let in_bridge: Vec<String> = cache
+            .channels_in_bridge("br")
+            .into_iter()
+            .map(|channel| channel.id)
+            .collect();
+        assert_eq!(in_bridge, vec!["a"]);
+        assert_eq!(cache.channel_bridge("c").unwrap().id, "br");
+    }
+
+    // cargo test -- --show-output test_state_cache_reconnect
+    #[tokio::test(start_paused = true)]
+    async fn test_state_cache_reconnect() {
+        let simulator = AsteriskSimulator::start().await;
+        let mut client = simulator.client();
+        client.set_state_cache(Some(StateCache::new()));
+        client.set_reconnect_policy(Some(ReconnectPolicy::default()));
+        let mut events = client.subscribe_events();
+        let loop_client = client.clone();
+        tokio::spawn(async move { loop_client.ari_processing_loop(vec!["ivr".into()]).await });
+        simulator.wait_for_websocket().await;
+
+        let first = simulator.incoming_call("ivr", "1004", &[]);
+        // cache is updated before the event is delivered
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            AriEvent::StasisStart(_)
+        ));
+        let cache = client.state_cache().unwrap();
+        assert_eq!(cache.channel(&first.id).unwrap().caller.number, "1004");
+        assert_eq!(cache.channel(&first.id).unwrap().state, ChannelState::Ring);
+
+        // events emitted while disconnected are lost, state is picked up after reconnect
+        simulator.server().close_websockets();
+        let second = simulator.incoming_call("ivr", "1005", &[]);
+        simulator.server().wait_for_websocket(2).await;
+        // paused time auto-advances while waiting for REST responses, i.e. real time bounds the wait
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while cache.channel(&second.id).is_none() {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "state cache not resynchronized"
+            );
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(cache.active_calls().len(), 2);
+        assert_eq!(
+            simulator
+                .server()
+                .requests_to(&Method::GET, "/bridges")
+                .len(),
+            1
+        );
+
+        client.shutdown_handle().shutdown();
+    }
+}
@@ -0,0 +1,794 @@
+//! Mock ARI server for testing code built on [AriClient] without running Asterisk.
+//! Available with `testing` feature.
+//!
+//! Server listens on random local port and emulates ARI REST interface and events websocket:
+//! REST calls are recorded (see [MockAriServer::requests]) and answered with scripted responses
+//! (see [MockAriServer::respond]), events are pushed to connected websockets with [MockAriServer::send_event].
+//! [AsteriskSimulator] builds stateful fake Asterisk (channels, playbacks, DTMF, hangups) on top of it.
+//!
+//! For unit tests without any network use [MockAri], mock implementation of [Ari](crate::apis::Ari) trait.
+//!
+//! ```no_run
+//! use asterisk_ari_client_rs::apis::channels::ChannelsAPI;
+//! use asterisk_ari_client_rs::testing::MockAriServer;
+//! use reqwest::{Method, StatusCode};
+//!
+//! # async fn example() {
+//! let server = MockAriServer::start().await;
+//! server.respond(Method::POST, "/channels/1607454635.2/answer", StatusCode::NO_CONTENT, "");
+//!
+//! let client = server.client();
+//! client.answer("1607454635.2").await.unwrap();
+//!
+//! assert_eq!(server.requests()[0].path, "/channels/1607454635.2/answer");
+//! # }
+//! ```
+
+pub mod fixtures;
+mod mock_ari;
+mod simulator;
+pub use mock_ari::MockAri;
+pub use simulator::AsteriskSimulator;
+
+use crate::client::AriClient;
+use crate::models::events::AriEvent;
+use log::*;
+use reqwest::{Method, StatusCode};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, watch};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message as WSMessage;
+use tokio_tungstenite::WebSocketStream;
+
+/// path prefix the server emulates ARI on, i.e. base URL is http://127.0.0.1:port/ari
+const BASE_PATH: &str = "/ari";
+
+/// how long wait_for_* methods wait before panicking
+const WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// REST call (or websocket upgrade request) received by [MockAriServer].
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedRequest {
+    pub method: Method,
+
+    /// Path relative to ARI base URL (e.g. /channels/1607454635.2/answer), still url encoded.
+    pub path: String,
+
+    /// Raw (url encoded) query string.
+    pub query: Option<String>,
+
+    /// Header names are lowercase.
+    pub headers: HashMap<String, String>,
+
+    pub body: String,
+}
+
+impl RecordedRequest {
+    /// Decoded value of query parameter.
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        let query = self.query.as_deref()?;
+        url::form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    /// Body parsed as JSON, Null for empty or invalid body.
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap_or(serde_json::Value::Null)
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(String::as_str)
+    }
+}
+
+/// Response returned by [MockAriServer] for matching request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockResponse {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+/// Custom request handler, see [MockAriServer::set_handler].
+pub type RequestHandler = dyn Fn(&RecordedRequest) -> Option<MockResponse> + Send + Sync;
+
+#[derive(Default)]
+struct MockState {
+    requests: Vec<RecordedRequest>,
+    websocket_requests: Vec<RecordedRequest>,
+    responses: HashMap<(Method, String), MockResponse>,
+    responses_once: HashMap<(Method, String), VecDeque<MockResponse>>,
+    handler: Option<Arc<RequestHandler>>,
+    unresponsive: bool,
+}
+
+impl MockState {
+    fn response(&mut self, method: &Method, path: &str) -> MockResponse {
+        let key = (method.clone(), path.to_owned());
+        if let Some(response) = self
+            .responses_once
+            .get_mut(&key)
+            .and_then(|responses| responses.pop_front())
+        {
+            return response;
+        }
+        self.responses.get(&key).cloned().unwrap_or_else(|| {
+            warn!(
+                "mock ARI server: no response scripted for {} {}",
+                method, path
+            );
+            MockResponse {
+                status: StatusCode::NOT_FOUND,
+                body: serde_json::json!({
+                    "message": format!("no response scripted for {} {}", method, path)
+                })
+                .to_string(),
+            }
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+enum WsCommand {
+    Text(String),
+    Close,
+}
+
+/// Local HTTP + websocket server emulating ARI. Server is stopped when dropped.
+pub struct MockAriServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    /// bumped on every recorded request or websocket connection
+    changes: watch::Receiver<u64>,
+    ws_commands: broadcast::Sender<WsCommand>,
+    accept_task: JoinHandle<()>,
+}
+
+impl MockAriServer {
+    /// Starts the server on random local port.
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("mock ARI server: unable to bind local port");
+        let addr = listener
+            .local_addr()
+            .expect("mock ARI server: no local address");
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let (changes_sender, changes) = watch::channel(0);
+        let (ws_commands, _) = broadcast::channel(1024);
+
+        let accept_task = tokio::spawn(accept_loop(
+            listener,
+            state.clone(),
+            Arc::new(changes_sender),
+            ws_commands.clone(),
+        ));
+
+        MockAriServer {
+            addr,
+            state,
+            changes,
+            ws_commands,
+            accept_task,
+        }
+    }
+
+    /// ARI base URL of the server, e.g. http://127.0.0.1:34567/ari
+    pub fn url(&self) -> String {
+        format!("http://{}{}", self.addr, BASE_PATH)
+    }
+
+    /// Client connected to this server (credentials are not verified by the server).
+    pub fn client(&self) -> AriClient {
+        AriClient::new(self.url(), "asterisk".into(), "asterisk".into())
+    }
+
+    /// Response returned for every request with given method and path (relative to ARI base URL,
+    /// url encoded, without query string). Replaces previously scripted response.
+    /// Requests without scripted response get 404.
+    pub fn respond(&self, method: Method, path: &str, status: StatusCode, body: impl Into<String>) {
+        self.state.lock().unwrap().responses.insert(
+            (method, path.to_owned()),
+            MockResponse {
+                status,
+                body: body.into(),
+            },
+        );
+    }
+
+    /// Same as [respond](MockAriServer::respond), body is serialized to JSON.
+    pub fn respond_json<T: Serialize>(
+        &self,
+        method: Method,
+        path: &str,
+        status: StatusCode,
+        body: &T,
+    ) {
+        let body = serde_json::to_string(body).expect("mock ARI server: unable to serialize body");
+        self.respond(method, path, status, body);
+    }
+
+    /// Response returned for the next matching request only, takes precedence over
+    /// [respond](MockAriServer::respond). Multiple responses are returned in order they were scripted.
+    pub fn respond_once(
+        &self,
+        method: Method,
+        path: &str,
+        status: StatusCode,
+        body: impl Into<String>,
+    ) {
+        self.state
+            .lock()
+            .unwrap()
+            .responses_once
+            .entry((method, path.to_owned()))
+            .or_default()
+            .push_back(MockResponse {
+                status,
+                body: body.into(),
+            });
+    }
+
+    /// Handler consulted before scripted responses, returning None falls back to them.
+    /// Used e.g. by [AsteriskSimulator] to emulate stateful Asterisk.
+    pub fn set_handler<F>(&self, handler: F)
+    where
+        F: Fn(&RecordedRequest) -> Option<MockResponse> + Send + Sync + 'static,
+    {
+        self.state.lock().unwrap().handler = Some(Arc::new(handler));
+    }
+
+    /// REST calls received so far.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// REST calls with given method and path received so far.
+    pub fn requests_to(&self, method: &Method, path: &str) -> Vec<RecordedRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request.method == *method && request.path == path)
+            .collect()
+    }
+
+    pub fn clear_requests(&self) {
+        self.state.lock().unwrap().requests.clear();
+    }
+
+    /// Websocket upgrade requests received so far (one per connection).
+    pub fn websocket_requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().websocket_requests.clone()
+    }
+
+    /// Waits until REST call with given method and path is received, panics after 5 seconds.
+    pub async fn wait_for_request(&self, method: Method, path: &str) -> RecordedRequest {
+        self.wait_for("request", |state| {
+            state
+                .requests
+                .iter()
+                .find(|request| request.method == method && request.path == path)
+                .cloned()
+        })
+        .await
+    }
+
+    /// Waits until given number of websocket connections were accepted in total, panics after 5 seconds.
+    /// Events sent before the client connects are lost, i.e. call this before [send_event](MockAriServer::send_event).
+    pub async fn wait_for_websocket(&self, connections: usize) -> RecordedRequest {
+        self.wait_for("websocket connection", |state| {
+            if state.websocket_requests.len() >= connections {
+                state.websocket_requests.last().cloned()
+            } else {
+                None
+            }
+        })
+        .await
+    }
+
+    async fn wait_for<T, F>(&self, what: &str, check: F) -> T
+    where
+        F: Fn(&MockState) -> Option<T>,
+    {
+        let mut changes = self.changes.clone();
+        let wait = async {
+            loop {
+                if let Some(found) = check(&self.state.lock().unwrap()) {
+                    return found;
+                }
+                if changes.changed().await.is_err() {
+                    panic!("mock ARI server stopped");
+                }
+            }
+        };
+        match timeout(WAIT_TIMEOUT, wait).await {
+            Ok(found) => found,
+            Err(_) => panic!("mock ARI server: timeout waiting for {}", what),
+        }
+    }
+
+    /// Pushes event to all connected websockets, returns number of connections the event was sent to.
+    pub fn send_event(&self, event: &AriEvent) -> usize {
+        let text =
+            serde_json::to_string(event).expect("mock ARI server: unable to serialize event");
+        self.send_raw_event(text)
+    }
+
+    /// Pushes raw text message (e.g. event not supported by this library) to all connected websockets.
+    pub fn send_raw_event(&self, text: impl Into<String>) -> usize {
+        self.ws_commands
+            .send(WsCommand::Text(text.into()))
+            .unwrap_or(0)
+    }
+
+    /// Websockets connected from now on do not read client's frames, i.e. pings are not answered
+    /// (e.g. to test dead connection detection). Events are still sent.
+    pub fn set_unresponsive(&self, unresponsive: bool) {
+        self.state.lock().unwrap().unresponsive = unresponsive;
+    }
+
+    /// Closes all connected websockets (e.g. to test reconnection).
+    pub fn close_websockets(&self) {
+        let _ = self.ws_commands.send(WsCommand::Close);
+    }
+}
+
+impl Drop for MockAriServer {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+    }
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    state: Arc<Mutex<MockState>>,
+    changes: Arc<watch::Sender<u64>>,
+    ws_commands: broadcast::Sender<WsCommand>,
+) {
+    // connection tasks are aborted together with the accept loop
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    connections.spawn(handle_connection(
+                        stream,
+                        state.clone(),
+                        changes.clone(),
+                        ws_commands.clone(),
+                    ));
+                }
+                Err(err) => {
+                    error!("mock ARI server: accept failed {:?}", err);
+                    return;
+                }
+            },
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+        }
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    state: Arc<Mutex<MockState>>,
+    changes: Arc<watch::Sender<u64>>,
+    ws_commands: broadcast::Sender<WsCommand>,
+) {
+    let mut buffer = Vec::new();
+    // HTTP keep-alive, i.e. several requests can come over single connection
+    while let Some(request) = read_request(&mut stream, &mut buffer).await {
+        trace!(
+            "mock ARI server: {} {} {:?}",
+            request.method,
+            request.path,
+            request.query
+        );
+
+        if matches!(request.header("upgrade"), Some(upgrade) if upgrade.eq_ignore_ascii_case("websocket"))
+        {
+            let key = request.header("sec-websocket-key").unwrap_or_default();
+            let handshake = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                derive_accept_key(key.as_bytes())
+            );
+            if stream.write_all(handshake.as_bytes()).await.is_err() {
+                return;
+            }
+            // subscribe before the connection is announced so that no event is lost
+            let commands = ws_commands.subscribe();
+            let unresponsive = {
+                let mut state = state.lock().unwrap();
+                state.websocket_requests.push(request);
+                state.unresponsive
+            };
+            changes.send_modify(|counter| *counter += 1);
+
+            let ws_stream = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+            handle_websocket(ws_stream, commands, unresponsive).await;
+            return;
+        }
+
+        let handler = state.lock().unwrap().handler.clone();
+        let handled = handler.and_then(|handler| handler(&request));
+        let response = {
+            let mut state = state.lock().unwrap();
+            let response = match handled {
+                Some(response) => response,
+                None => state.response(&request.method, &request.path),
+            };
+            state.requests.push(request);
+            response
+        };
+        changes.send_modify(|counter| *counter += 1);
+
+        let head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+            response.status.as_u16(),
+            response.status.canonical_reason().unwrap_or(""),
+            response.body.len()
+        );
+        if stream.write_all(head.as_bytes()).await.is_err()
+            || stream.write_all(response.body.as_bytes()).await.is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// reads single HTTP request, None when connection was closed or request is malformed
+async fn read_request(stream: &mut TcpStream, buffer: &mut Vec<u8>) -> Option<RecordedRequest> {
+    let head_end = loop {
+        if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position;
+        }
+        if read_more(stream, buffer).await == 0 {
+            return None;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..head_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let method = Method::from_bytes(request_line.next()?.as_bytes()).ok()?;
+    let target = request_line.next()?;
+
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_owned()))
+        .collect();
+
+    let content_length: usize = headers
+        .get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    let body_start = head_end + 4;
+    while buffer.len() < body_start + content_length {
+        if read_more(stream, buffer).await == 0 {
+            return None;
+        }
+    }
+    let body =
+        String::from_utf8_lossy(&buffer[body_start..body_start + content_length]).into_owned();
+    buffer.drain(..body_start + content_length);
+
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, Some(query.to_owned())),
+        None => (target, None),
+    };
+    let path = path.strip_prefix(BASE_PATH).unwrap_or(path).to_owned();
+
+    Some(RecordedRequest {
+        method,
+        path,
+        query,
+        headers,
+        body,
+    })
+}
+
+async fn read_more(stream: &mut TcpStream, buffer: &mut Vec<u8>) -> usize {
+    let mut chunk = [0u8; 4096];
+    match stream.read(&mut chunk).await {
+        Ok(read) => {
+            buffer.extend_from_slice(&chunk[..read]);
+            read
+        }
+        Err(_) => 0,
+    }
+}
+
+async fn handle_websocket(
+    mut ws_stream: WebSocketStream<TcpStream>,
+    mut commands: broadcast::Receiver<WsCommand>,
+    unresponsive: bool,
+) {
+    use futures_util::{SinkExt, StreamExt};
+
+    loop {
+        tokio::select! {
+            // reading is needed also to answer pings and close frames
+            message = ws_stream.next(), if !unresponsive => match message {
+                Some(Ok(WSMessage::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+            command = commands.recv() => match command {
+                Ok(WsCommand::Text(text)) => {
+                    if ws_stream.send(WSMessage::Text(text)).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(WsCommand::Close) | Err(broadcast::error::RecvError::Closed) => {
+                    let _ = ws_stream.close(None).await;
+                    return;
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("mock ARI server: {} events were not sent", skipped);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::channels::ChannelsAPI;
+    use crate::connection::KeepaliveConfig;
+    use crate::dispatch::KeyedDispatcher;
+    use crate::errors::{ApiErrorKind, Error};
+    use crate::retry::RetryPolicy;
+    use crate::testing::fixtures::stasis_start;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::time::sleep;
+
+    // cargo test -- --show-output test_mock_server_rest
+    #[tokio::test]
+    async fn test_mock_server_rest() {
+        let server = MockAriServer::start().await;
+        let client = server.client();
+
+        server.respond(
+            Method::POST,
+            "/channels/chan%201/variable",
+            StatusCode::NO_CONTENT,
+            "",
+        );
+        client
+            .set_variable("chan 1", "GREETING", "hello world")
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, Method::POST);
+        assert_eq!(requests[0].path, "/channels/chan%201/variable");
+        assert_eq!(
+            requests[0].query_param("value").as_deref(),
+            Some("hello world")
+        );
+        assert!(
+            matches!(requests[0].header("Authorization"), Some(auth) if auth.starts_with("Basic "))
+        );
+
+        // unscripted request
+        let err = client.answer("unknown").await.unwrap_err();
+        assert_eq!(err.api_error_kind(), Some(ApiErrorKind::NotFound));
+
+        // one shot responses take precedence, GET is retried
+        server.respond(
+            Method::GET,
+            "/channels/chan1/variable",
+            StatusCode::OK,
+            "{\"value\": \"42\"}",
+        );
+        server.respond_once(
+            Method::GET,
+            "/channels/chan1/variable",
+            StatusCode::SERVICE_UNAVAILABLE,
+            "",
+        );
+        let client = client.with_retry_policy(Some(RetryPolicy {
+            initial_backoff: Duration::from_millis(10),
+            ..Default::default()
+        }));
+        assert_eq!(client.get_variable("chan1", "ANSWER").await.unwrap(), "42");
+        assert_eq!(
+            server
+                .requests_to(&Method::GET, "/channels/chan1/variable")
+                .len(),
+            2
+        );
+
+        // playback stop is retried, hangup is not
+        server.respond(Method::DELETE, "/playbacks/p1", StatusCode::NO_CONTENT, "");
+        server.respond_once(
+            Method::DELETE,
+            "/playbacks/p1",
+            StatusCode::SERVICE_UNAVAILABLE,
+            "",
+        );
+        client.stop_play("p1").await.unwrap();
+        assert_eq!(
+            server.requests_to(&Method::DELETE, "/playbacks/p1").len(),
+            2
+        );
+        server.respond(
+            Method::DELETE,
+            "/channels/chan1",
+            StatusCode::SERVICE_UNAVAILABLE,
+            "",
+        );
+        assert!(client.hangup("chan1").await.is_err());
+        assert_eq!(
+            server.requests_to(&Method::DELETE, "/channels/chan1").len(),
+            1
+        );
+    }
+
+    // cargo test -- --show-output test_mock_server_events
+    #[tokio::test]
+    async fn test_mock_server_events() {
+        let server = MockAriServer::start().await;
+        let client = server.client();
+        let mut events = client.subscribe_events();
+
+        let loop_client = client.clone();
+        let processing_loop =
+            tokio::spawn(
+                async move { loop_client.ari_processing_loop(vec!["my-app".into()]).await },
+            );
+
+        let upgrade = server.wait_for_websocket(1).await;
+        assert_eq!(upgrade.path, "/events");
+        assert_eq!(upgrade.query_param("app").as_deref(), Some("my-app"));
+
+        assert_eq!(server.send_event(&stasis_start("1610050377.0")), 1);
+
+        match timeout(WAIT_TIMEOUT, events.recv()).await.unwrap().unwrap() {
+            AriEvent::StasisStart(event) => assert_eq!(event.channel.id, "1610050377.0"),
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        client.shutdown_handle().shutdown();
+        timeout(WAIT_TIMEOUT, processing_loop)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    // cargo test -- --show-output test_mock_server_keepalive_disabled
+    #[tokio::test]
+    async fn test_mock_server_keepalive_disabled() {
+        let server = MockAriServer::start().await;
+        let mut client = server.client();
+        client.set_keepalive(KeepaliveConfig {
+            ping_interval: Duration::ZERO,
+            pong_timeout: Some(Duration::from_millis(1)),
+        });
+        let mut events = client.subscribe_events();
+
+        let loop_client = client.clone();
+        let processing_loop =
+            tokio::spawn(
+                async move { loop_client.ari_processing_loop(vec!["my-app".into()]).await },
+            );
+        server.wait_for_websocket(1).await;
+
+        // no ping is sent, i.e. missing pong cannot end the connection
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(server.send_event(&stasis_start("1610050377.0")), 1);
+        assert!(timeout(WAIT_TIMEOUT, events.recv()).await.unwrap().is_ok());
+
+        client.shutdown_handle().shutdown();
+        timeout(WAIT_TIMEOUT, processing_loop)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    // cargo test -- --show-output test_mock_server_shutdown_flushes_deliveries
+    #[tokio::test]
+    async fn test_mock_server_shutdown_flushes_deliveries() {
+        let server = MockAriServer::start().await;
+        let mut client = server.client();
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+        client.set_stasis_start_sender(Some(sender));
+        let handled = Arc::new(AtomicUsize::new(0));
+        let dispatcher = KeyedDispatcher::new({
+            let handled = handled.clone();
+            move |_| {
+                let handled = handled.clone();
+                async move {
+                    sleep(Duration::from_millis(5)).await;
+                    handled.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        })
+        .key(|_| None);
+        client.add_event_sink(Arc::new(dispatcher));
+        let mut events = client.subscribe_events();
+
+        let loop_client = client.clone();
+        let processing_loop =
+            tokio::spawn(
+                async move { loop_client.ari_processing_loop(vec!["my-app".into()]).await },
+            );
+        server.wait_for_websocket(1).await;
+
+        // slow consumer of the typed sender
+        let consumer = tokio::spawn(async move {
+            let mut received = 0;
+            while receiver.recv().await.is_some() {
+                sleep(Duration::from_millis(5)).await;
+                received += 1;
+            }
+            received
+        });
+
+        for i in 0..20 {
+            server.send_event(&stasis_start(&format!("1610050377.{}", i)));
+        }
+        for _ in 0..20 {
+            timeout(WAIT_TIMEOUT, events.recv()).await.unwrap().unwrap();
+        }
+
+        client.shutdown_handle().shutdown();
+        timeout(WAIT_TIMEOUT, processing_loop)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        // everything was handed over before the loop returned
+        assert_eq!(handled.load(Ordering::SeqCst), 20);
+        let stats = client.sender_stats();
+        assert_eq!(stats.delivered, 20);
+        assert_eq!(stats.queued, 0);
+
+        drop(client);
+        assert_eq!(timeout(WAIT_TIMEOUT, consumer).await.unwrap().unwrap(), 20);
+    }
+
+    // cargo test -- --show-output test_mock_server_keepalive_any_frame
+    #[tokio::test]
+    async fn test_mock_server_keepalive_any_frame() {
+        let server = MockAriServer::start().await;
+        server.set_unresponsive(true);
+        let mut client = server.client();
+        client.set_keepalive(KeepaliveConfig {
+            ping_interval: Duration::from_millis(10),
+            pong_timeout: Some(Duration::from_millis(100)),
+        });
+
+        let loop_client = client.clone();
+        let processing_loop =
+            tokio::spawn(
+                async move { loop_client.ari_processing_loop(vec!["my-app".into()]).await },
+            );
+        server.wait_for_websocket(1).await;
+
+        // pings are not answered but events keep the connection alive
+        for _ in 0..30 {
+            assert_eq!(server.send_event(&stasis_start("1610050377.0")), 1);
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!processing_loop.is_finished());
+
+        // connection is considered dead once Asterisk goes silent
+        let err = timeout(WAIT_TIMEOUT, processing_loop)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap_err();
+        assert!(matches!(err, Error::KeepaliveTimeout(_)));
+    }
+}
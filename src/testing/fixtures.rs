@@ -0,0 +1,61 @@
+//! JSON fixtures of ARI resources and events, e.g. to feed events into
+//! [dispatch_event](crate::client::AriClient) based tests or [MockAriServer](super::MockAriServer) responses.
+
+use crate::models::events::AriEvent;
+use serde_json::{json, Value};
+
+/// Channel in Stasis application `my-app`, state Up, caller 6001.
+pub fn channel_json(channel_id: &str) -> Value {
+    json!({
+        "id": channel_id,
+        "name": "PJSIP/6001-00000000",
+        "state": "Up",
+        "caller": { "name": "Adam", "number": "6001" },
+        "connected": { "name": "", "number": "" },
+        "accountcode": "",
+        "dialplan": { "context": "from-internal", "exten": "100", "priority": 2, "app_name": "Stasis", "app_data": "my-app" },
+        "creationtime": "2021-01-07T21:06:17.692+0100",
+        "language": "en"
+    })
+}
+
+/// Event of given type sent to application `my-app`, fields are added to the common ones (type, timestamp, ...).
+pub fn event_json(event_type: &str, fields: Value) -> Value {
+    let mut event = json!({
+        "type": event_type,
+        "asterisk_id": "00:15:5d:01:65:04",
+        "application": "my-app",
+        "timestamp": "2021-01-07T21:06:17.695+0100",
+    });
+    if let (Some(event), Some(fields)) = (event.as_object_mut(), fields.as_object()) {
+        event.extend(fields.clone());
+    }
+    event
+}
+
+/// Parsed [event_json], panics when the fields do not match the event type.
+pub fn event(event_type: &str, fields: Value) -> AriEvent {
+    serde_json::from_value(event_json(event_type, fields)).expect("invalid event fixture")
+}
+
+/// Event of given type carrying [channel_json] of the channel.
+pub fn channel_event(event_type: &str, channel_id: &str, mut fields: Value) -> AriEvent {
+    fields["channel"] = channel_json(channel_id);
+    event(event_type, fields)
+}
+
+pub fn stasis_start(channel_id: &str) -> AriEvent {
+    channel_event("StasisStart", channel_id, json!({ "args": [] }))
+}
+
+pub fn stasis_end(channel_id: &str) -> AriEvent {
+    channel_event("StasisEnd", channel_id, json!({}))
+}
+
+pub fn dtmf(channel_id: &str, digit: &str) -> AriEvent {
+    channel_event(
+        "ChannelDtmfReceived",
+        channel_id,
+        json!({ "digit": digit, "duration_ms": 100 }),
+    )
+}
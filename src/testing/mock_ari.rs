@@ -0,0 +1,220 @@
+use crate::apis::applications::ApplicationsAPI;
+use crate::apis::bridges::BridgesAPI;
+use crate::apis::channels::ChannelsAPI;
+use crate::apis::recordings::RecordingsAPI;
+use crate::apis::Ari;
+use crate::errors::Result;
+use crate::models::applications::Application;
+use crate::models::bridges::Bridge;
+use crate::models::channels::{Channel, CreateChannelOptions, OriginateOptions, SnoopOptions};
+use crate::models::events::AriEvent;
+use crate::models::playbacks::Playback;
+use crate::models::recordings::{RecordOptions, Recording};
+use async_trait::async_trait;
+use tokio::sync::broadcast;
+
+mockall::mock! {
+    /// Mock implementation of [Ari] generated by [mockall], every operation has its
+    /// `expect_*` method (e.g. `expect_answer`) to set up expectations and returned values.
+    /// get_variables is not mocked, it calls mocked get_variable for every variable.
+    ///
+    /// ```no_run
+    /// use asterisk_ari_client_rs::apis::{channels::ChannelsAPI, Ari};
+    /// use asterisk_ari_client_rs::testing::MockAri;
+    /// use mockall::predicate::eq;
+    /// use std::sync::Arc;
+    ///
+    /// # async fn example() {
+    /// let mut ari = MockAri::new();
+    /// ari.expect_answer()
+    ///     .with(eq("1607454635.2"))
+    ///     .times(1)
+    ///     .returning(|_| Ok(()));
+    ///
+    /// let ari: Arc<dyn Ari> = Arc::new(ari);
+    /// ari.answer("1607454635.2").await.unwrap();
+    /// # }
+    /// ```
+    pub Ari {}
+
+    #[async_trait]
+    impl ApplicationsAPI for Ari {
+        async fn filter(
+            &self,
+            application_name: &str,
+            filter: Option<serde_json::Value>,
+        ) -> Result<String>;
+        async fn get(&self, application_name: &str) -> Result<Application>;
+        async fn list(&self) -> Result<Vec<Application>>;
+        async fn subscribe(&self, application_name: &str, event_source: Vec<String>) -> Result<String>;
+        async fn unsubscribe(
+            &self,
+            application_name: &str,
+            event_source: Vec<String>,
+        ) -> Result<String>;
+    }
+
+    #[async_trait]
+    impl BridgesAPI for Ari {
+        async fn list_bridges(&self) -> Result<Vec<Bridge>>;
+        async fn get_bridge(&self, bridge_id: &str) -> Result<Bridge>;
+    }
+
+    #[async_trait]
+    impl ChannelsAPI for Ari {
+        async fn list_channels(&self) -> Result<Vec<Channel>>;
+        async fn originate(&self, endpoint: &str, options: OriginateOptions) -> Result<Channel>;
+        async fn create_channel(
+            &self,
+            endpoint: &str,
+            app: &str,
+            options: CreateChannelOptions,
+        ) -> Result<Channel>;
+        async fn answer(&self, channel_id: &str) -> Result<()>;
+        async fn play(
+            &self,
+            channel_id: &str,
+            media: &str,
+            playback_id: Option<String>,
+            lang: Option<String>,
+            offsetms: Option<usize>,
+            skipms: Option<usize>,
+        ) -> Result<Playback>;
+        async fn stop_play(&self, playback_id: &str) -> Result<()>;
+        async fn get_variable(&self, channel_id: &str, var_name: &str) -> Result<String>;
+        async fn set_variable(&self, channel_id: &str, var_name: &str, var_value: &str) -> Result<()>;
+        async fn hangup(&self, channel_id: &str) -> Result<()>;
+        async fn continue_in_dialplan(&self, channel_id: &str) -> Result<()>;
+        async fn snoop(&self, channel_id: &str, app: &str, options: SnoopOptions) -> Result<Channel>;
+        async fn record(&self, channel_id: &str, options: RecordOptions) -> Result<Recording>;
+    }
+
+    #[async_trait]
+    impl RecordingsAPI for Ari {
+        async fn get_recording(&self, recording_name: &str) -> Result<Vec<u8>>;
+        async fn stop_recording(&self, recording_name: &str) -> Result<()>;
+        async fn pause_recording(&self, recording_name: &str) -> Result<()>;
+        async fn unpause_recording(&self, recording_name: &str) -> Result<()>;
+        async fn mute_recording(&self, recording_name: &str) -> Result<()>;
+        async fn unmute_recording(&self, recording_name: &str) -> Result<()>;
+        async fn delete_recording(&self, recording_name: &str) -> Result<()>;
+    }
+
+    impl Ari for Ari {
+        fn subscribe_events(&self) -> broadcast::Receiver<AriEvent>;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dtmf::{collect_dtmf, DtmfOptions, DtmfResult};
+    use crate::errors::Error;
+    use crate::models::playbacks::PlaybackState;
+    use crate::testing::fixtures::dtmf;
+    use mockall::predicate::eq;
+    use std::sync::Arc;
+    use tokio::time::Duration;
+
+    // cargo test -- --show-output test_mock_ari_collect_dtmf
+    #[tokio::test]
+    async fn test_mock_ari_collect_dtmf() {
+        let (events, _) = broadcast::channel(16);
+
+        let mut mock = MockAri::new();
+        let sender = events.clone();
+        mock.expect_subscribe_events()
+            .times(1)
+            .returning(move || sender.subscribe());
+        mock.expect_play().never();
+        mock.expect_answer()
+            .with(eq("1607454635.2"))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        // code under test works with trait object, i.e. it does not care about real client
+        let ari: Arc<dyn Ari> = Arc::new(mock);
+        ari.answer("1607454635.2").await.unwrap();
+
+        let collect = tokio::spawn({
+            let ari = ari.clone();
+            async move {
+                let options = DtmfOptions {
+                    max_digits: Some(2),
+                    ..Default::default()
+                };
+                collect_dtmf(ari.as_ref(), "1607454635.2", options).await
+            }
+        });
+
+        // wait until collect_dtmf subscribes to the events
+        while events.receiver_count() == 0 {
+            tokio::task::yield_now().await;
+        }
+        events.send(dtmf("other-channel", "9")).unwrap();
+        events.send(dtmf("1607454635.2", "4")).unwrap();
+        events.send(dtmf("1607454635.2", "2")).unwrap();
+
+        let result = collect.await.unwrap().unwrap();
+        assert_eq!(result, DtmfResult::Complete("42".into()));
+    }
+
+    // cargo test -- --show-output test_mock_ari_collect_dtmf_prompt_lost
+    #[tokio::test(start_paused = true)]
+    async fn test_mock_ari_collect_dtmf_prompt_lost() {
+        let (events, _) = broadcast::channel(16);
+
+        let mut mock = MockAri::new();
+        let sender = events.clone();
+        mock.expect_subscribe_events()
+            .returning(move || sender.subscribe());
+        mock.expect_play()
+            .times(1)
+            .returning(|channel_id, media, _, _, _, _| {
+                Ok(Playback {
+                    id: "prompt".into(),
+                    media_uri: media.into(),
+                    next_media_uri: None,
+                    target_uri: format!("channel:{}", channel_id),
+                    language: "en".into(),
+                    state: PlaybackState::Queued,
+                })
+            });
+
+        // PlaybackFinished of the prompt never arrives, collection must not hang
+        let started = tokio::time::Instant::now();
+        let options = DtmfOptions {
+            prompt: Some("sound:enter-pin".into()),
+            prompt_timeout: Duration::from_secs(10),
+            first_digit_timeout: Duration::from_secs(5),
+            ..Default::default()
+        };
+        let result = collect_dtmf(&mock, "1607454635.2", options).await.unwrap();
+        assert_eq!(result, DtmfResult::Timeout("".into()));
+        assert_eq!(started.elapsed(), Duration::from_secs(15));
+    }
+
+    // cargo test -- --show-output test_mock_ari_collect_dtmf_lagged
+    #[tokio::test]
+    async fn test_mock_ari_collect_dtmf_lagged() {
+        let (events, _) = broadcast::channel(2);
+
+        let mut mock = MockAri::new();
+        let receiver = events.subscribe();
+        mock.expect_subscribe_events()
+            .times(1)
+            .return_once(move || receiver);
+
+        // digits overflow the subscription before the collection starts reading
+        events.send(dtmf("1607454635.2", "1")).unwrap();
+        events.send(dtmf("1607454635.2", "2")).unwrap();
+        events.send(dtmf("1607454635.2", "3")).unwrap();
+
+        let options = DtmfOptions {
+            max_digits: Some(2),
+            ..Default::default()
+        };
+        let result = collect_dtmf(&mock, "1607454635.2", options).await;
+        assert!(matches!(result, Err(Error::EventsLagged(1))));
+    }
+}
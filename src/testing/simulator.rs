@@ -0,0 +1,845 @@
+use super::{MockAriServer, MockResponse, RecordedRequest, WsCommand};
+use crate::client::AriClient;
+use crate::connection::KeepaliveConfig;
+use crate::models::channels::{CallerId, Channel, ChannelState, DialplanCep, HangupCause};
+use crate::models::events::*;
+use crate::models::playbacks::{Playback, PlaybackState};
+use crate::models::recordings::{Recording, RecordingState};
+use log::*;
+use percent_encoding::percent_decode_str;
+use reqwest::{Method, StatusCode};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::sleep;
+
+/// Stateful fake Asterisk built on top of [MockAriServer]. Emulates channels, playbacks, recordings
+/// and channel variables, REST calls not emulated fall back to scripted responses of the server.
+///
+/// Playbacks finish after simulated duration (see [set_media_duration](AsteriskSimulator::set_media_duration)),
+/// i.e. call flows can be tested deterministically with paused tokio time (`#[tokio::test(start_paused = true)]`).
+///
+/// Emulated operations and events they emit:
+///
+/// * originate, create_channel: StasisStart (when application is given)
+/// * answer: ChannelStateChange
+/// * play: PlaybackStarted, PlaybackFinished once media duration elapses
+/// * stop_play: PlaybackFinished
+/// * set_variable: ChannelVarset
+/// * record, stop_recording: RecordingStarted, RecordingFinished
+/// * continue_in_dialplan: StasisEnd
+/// * hangup: ChannelHangupRequest, StasisEnd, ChannelDestroyed
+pub struct AsteriskSimulator {
+    server: MockAriServer,
+    simulation: Arc<Simulation>,
+}
+
+impl AsteriskSimulator {
+    pub async fn start() -> Self {
+        let server = MockAriServer::start().await;
+        let simulation = Arc::new(Simulation {
+            state: Mutex::new(SimulationState {
+                default_playback_duration: Duration::from_secs(1),
+                ..Default::default()
+            }),
+            events: server.ws_commands.clone(),
+        });
+
+        let handler_simulation = simulation.clone();
+        server.set_handler(move |request| handler_simulation.handle(request));
+
+        AsteriskSimulator { server, simulation }
+    }
+
+    /// Underlying mock server, e.g. for assertions on received REST calls.
+    pub fn server(&self) -> &MockAriServer {
+        &self.server
+    }
+
+    /// Client connected to the simulator. Dead connection detection is disabled
+    /// so that paused (auto advancing) time cannot trigger keepalive timeout.
+    pub fn client(&self) -> AriClient {
+        let mut client = self.server.client();
+        client.set_keepalive(KeepaliveConfig {
+            pong_timeout: None,
+            ..Default::default()
+        });
+        client
+    }
+
+    /// Waits until the client connects the websocket, events emitted before are lost.
+    pub async fn wait_for_websocket(&self) {
+        self.server.wait_for_websocket(1).await;
+    }
+
+    /// How long playback of given media URI (e.g. sound:hello-world) takes.
+    pub fn set_media_duration(&self, media_uri: &str, duration: Duration) {
+        self.simulation
+            .state
+            .lock()
+            .unwrap()
+            .media_durations
+            .insert(media_uri.to_owned(), duration);
+    }
+
+    /// Playback duration of media without explicitly set duration, 1 second by default.
+    pub fn set_default_playback_duration(&self, duration: Duration) {
+        self.simulation
+            .state
+            .lock()
+            .unwrap()
+            .default_playback_duration = duration;
+    }
+
+    /// Simulates incoming call entering Stasis application, emits StasisStart.
+    pub fn incoming_call(&self, app: &str, caller_number: &str, args: &[&str]) -> Channel {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut state = self.simulation.state.lock().unwrap();
+        let channel = state.new_channel(
+            &format!("PJSIP/{}", caller_number),
+            caller_number,
+            ChannelState::Ring,
+            Some(app),
+            HashMap::new(),
+        );
+        self.simulation.emit_stasis_start(&state, &channel.id, args);
+        channel
+    }
+
+    /// Simulates caller pressing DTMF keys, emits ChannelDtmfReceived for every digit.
+    pub fn send_dtmf(&self, channel_id: &str, digits: &str) {
+        let state = self.simulation.state.lock().unwrap();
+        let sim_channel = match state.channels.get(channel_id) {
+            Some(sim_channel) => sim_channel,
+            None => panic!("simulator: unknown channel {}", channel_id),
+        };
+        for digit in digits.chars() {
+            self.simulation
+                .emit(AriEvent::ChannelDtmfReceived(ChannelDtmfReceived {
+                    envelope: envelope(&sim_channel.app),
+                    digit: digit.to_string(),
+                    duration_ms: 100,
+                    channel: sim_channel.channel.clone(),
+                }));
+        }
+    }
+
+    /// Simulates caller hanging up.
+    pub fn caller_hangup(&self, channel_id: &str) {
+        let mut state = self.simulation.state.lock().unwrap();
+        if !self
+            .simulation
+            .destroy_channel(&mut state, channel_id, HangupCause::NormalClearing)
+        {
+            panic!("simulator: unknown channel {}", channel_id);
+        }
+    }
+
+    /// Current state of the channel, None when channel does not exist (e.g. was hung up).
+    pub fn channel(&self, channel_id: &str) -> Option<Channel> {
+        self.simulation
+            .state
+            .lock()
+            .unwrap()
+            .channels
+            .get(channel_id)
+            .map(|sim_channel| sim_channel.channel.clone())
+    }
+
+    pub fn channels(&self) -> Vec<Channel> {
+        self.simulation.state.lock().unwrap().channel_list()
+    }
+
+    /// Value of channel variable set via set_variable (or on originate).
+    pub fn variable(&self, channel_id: &str, name: &str) -> Option<String> {
+        self.simulation
+            .state
+            .lock()
+            .unwrap()
+            .channels
+            .get(channel_id)
+            .and_then(|sim_channel| sim_channel.variables.get(name).cloned())
+    }
+
+    /// All playbacks started so far (including finished ones) in the order they were started.
+    pub fn playbacks(&self) -> Vec<Playback> {
+        let state = self.simulation.state.lock().unwrap();
+        state
+            .playback_order
+            .iter()
+            .filter_map(|id| state.playbacks.get(id))
+            .map(|sim_playback| sim_playback.playback.clone())
+            .collect()
+    }
+
+    /// All recordings started so far.
+    pub fn recordings(&self) -> Vec<Recording> {
+        let state = self.simulation.state.lock().unwrap();
+        state
+            .recordings
+            .values()
+            .map(|sim_recording| sim_recording.recording.clone())
+            .collect()
+    }
+}
+
+struct SimChannel {
+    channel: Channel,
+    /// Stasis application the channel is in, empty when not in Stasis
+    app: String,
+    variables: HashMap<String, String>,
+}
+
+struct SimPlayback {
+    playback: Playback,
+    app: String,
+}
+
+struct SimRecording {
+    recording: Recording,
+    app: String,
+}
+
+#[derive(Default)]
+struct SimulationState {
+    channels: HashMap<String, SimChannel>,
+    /// channel ids in order of creation
+    channel_order: Vec<String>,
+    playbacks: HashMap<String, SimPlayback>,
+    playback_order: Vec<String>,
+    recordings: HashMap<String, SimRecording>,
+    media_durations: HashMap<String, Duration>,
+    default_playback_duration: Duration,
+    next_id: u64,
+}
+
+impl SimulationState {
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn new_channel(
+        &mut self,
+        endpoint: &str,
+        caller_number: &str,
+        state: ChannelState,
+        app: Option<&str>,
+        variables: HashMap<String, String>,
+    ) -> Channel {
+        self.new_channel_with_id(None, endpoint, caller_number, state, app, variables)
+    }
+
+    fn new_channel_with_id(
+        &mut self,
+        channel_id: Option<&str>,
+        endpoint: &str,
+        caller_number: &str,
+        state: ChannelState,
+        app: Option<&str>,
+        variables: HashMap<String, String>,
+    ) -> Channel {
+        let id = self.next_id();
+        let channel = Channel {
+            id: channel_id
+                .map(str::to_owned)
+                .unwrap_or_else(|| format!("sim.{}", id)),
+            name: format!("{}-{:08x}", endpoint, id),
+            state,
+            caller: CallerId {
+                name: String::new(),
+                number: caller_number.to_owned(),
+            },
+            connected: CallerId {
+                name: String::new(),
+                number: String::new(),
+            },
+            accountcode: String::new(),
+            dialplan: DialplanCep {
+                context: "default".into(),
+                exten: "s".into(),
+                priority: 1,
+                app_name: if app.is_some() { "Stasis" } else { "" }.into(),
+                app_data: app.unwrap_or_default().into(),
+            },
+            creationtime: now(),
+            language: Some("en".into()),
+            channelvars: None,
+        };
+        self.channel_order.push(channel.id.clone());
+        self.channels.insert(
+            channel.id.clone(),
+            SimChannel {
+                channel: channel.clone(),
+                app: app.unwrap_or_default().to_owned(),
+                variables,
+            },
+        );
+        channel
+    }
+
+    fn channel_list(&self) -> Vec<Channel> {
+        self.channel_order
+            .iter()
+            .filter_map(|id| self.channels.get(id))
+            .map(|sim_channel| sim_channel.channel.clone())
+            .collect()
+    }
+}
+
+struct Simulation {
+    state: Mutex<SimulationState>,
+    events: broadcast::Sender<WsCommand>,
+}
+
+impl Simulation {
+    fn emit(&self, event: AriEvent) {
+        trace!("simulator: emitting {}", event.event_type());
+        let text = serde_json::to_string(&event).expect("simulator: unable to serialize event");
+        let _ = self.events.send(WsCommand::Text(text));
+    }
+
+    fn emit_stasis_start(&self, state: &SimulationState, channel_id: &str, args: Vec<String>) {
+        let sim_channel = &state.channels[channel_id];
+        self.emit(AriEvent::StasisStart(StasisStart {
+            envelope: envelope(&sim_channel.app),
+            args,
+            channel: sim_channel.channel.clone(),
+            replace_channel: None,
+        }));
+    }
+
+    /// hangs up the channel emitting the events, false if the channel does not exist
+    fn destroy_channel(
+        &self,
+        state: &mut SimulationState,
+        channel_id: &str,
+        cause: HangupCause,
+    ) -> bool {
+        let sim_channel = match state.channels.remove(channel_id) {
+            Some(sim_channel) => sim_channel,
+            None => return false,
+        };
+        state.channel_order.retain(|id| id != channel_id);
+
+        let target_uri = format!("channel:{}", channel_id);
+        for sim_playback in state.playbacks.values_mut() {
+            if sim_playback.playback.target_uri == target_uri
+                && sim_playback.playback.state == PlaybackState::Playing
+            {
+                sim_playback.playback.state = PlaybackState::Done;
+                self.emit(AriEvent::PlaybackFinished(PlaybackFinished {
+                    envelope: envelope(&sim_playback.app),
+                    playback: sim_playback.playback.clone(),
+                }));
+            }
+        }
+
+        let app = &sim_channel.app;
+        let channel = sim_channel.channel;
+        self.emit(AriEvent::ChannelHangupRequest(ChannelHangupRequest {
+            envelope: envelope(app),
+            cause: Some(cause),
+            soft: None,
+            channel: channel.clone(),
+        }));
+        if !app.is_empty() {
+            self.emit(AriEvent::StasisEnd(StasisEnd {
+                envelope: envelope(app),
+                channel: channel.clone(),
+            }));
+        }
+        self.emit(AriEvent::ChannelDestroyed(ChannelDestroyed {
+            envelope: envelope(app),
+            cause,
+            cause_txt: cause.description().into(),
+            channel,
+        }));
+        true
+    }
+
+    fn handle(self: &Arc<Self>, request: &RecordedRequest) -> Option<MockResponse> {
+        let segments: Vec<String> = request
+            .path
+            .trim_start_matches('/')
+            .split('/')
+            .map(|segment| percent_decode_str(segment).decode_utf8_lossy().into_owned())
+            .collect();
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+        let body = request.json();
+
+        let mut state = self.state.lock().unwrap();
+        let response = match (&request.method, segments.as_slice()) {
+            (&Method::GET, ["channels"]) => json_response(StatusCode::OK, &state.channel_list()),
+            // bridges are not simulated
+            (&Method::GET, ["bridges"]) => json_response(StatusCode::OK, &Vec::<Value>::new()),
+            (&Method::POST, ["channels"]) => self.originate(&mut state, &body),
+            (&Method::POST, ["channels", "create"]) => self.create_channel(&mut state, &body),
+            (&Method::GET, ["channels", channel_id]) => match state.channels.get(*channel_id) {
+                Some(sim_channel) => json_response(StatusCode::OK, &sim_channel.channel),
+                None => error_response(StatusCode::NOT_FOUND, "Channel not found"),
+            },
+            (&Method::DELETE, ["channels", channel_id]) => {
+                if self.destroy_channel(&mut state, channel_id, HangupCause::NormalClearing) {
+                    no_content()
+                } else {
+                    error_response(StatusCode::NOT_FOUND, "Channel not found")
+                }
+            }
+            (method, ["channels", channel_id, operation]) => {
+                let sim_channel = match state.channels.get(*channel_id) {
+                    Some(sim_channel) => sim_channel,
+                    None => {
+                        return Some(error_response(StatusCode::NOT_FOUND, "Channel not found"))
+                    }
+                };
+                if sim_channel.app.is_empty() {
+                    return Some(error_response(
+                        StatusCode::CONFLICT,
+                        "Channel not in Stasis application",
+                    ));
+                }
+                match (method, *operation) {
+                    (&Method::POST, "answer") => self.answer(&mut state, channel_id),
+                    (&Method::POST, "play") => self.play(&mut state, channel_id, &body),
+                    (&Method::POST, "continue") => {
+                        self.continue_in_dialplan(&mut state, channel_id)
+                    }
+                    (&Method::GET, "variable") => {
+                        let name = request.query_param("variable").unwrap_or_default();
+                        match sim_channel.variables.get(&name) {
+                            Some(value) => json_response(
+                                StatusCode::OK,
+                                &serde_json::json!({ "value": value }),
+                            ),
+                            None => error_response(
+                                StatusCode::NOT_FOUND,
+                                "Provided variable was not found",
+                            ),
+                        }
+                    }
+                    (&Method::POST, "variable") => {
+                        let name = request.query_param("variable").unwrap_or_default();
+                        let value = request.query_param("value").unwrap_or_default();
+                        self.set_variable(&mut state, channel_id, name, value)
+                    }
+                    (&Method::POST, "record") => self.record(&mut state, channel_id, &body),
+                    _ => return None,
+                }
+            }
+            (&Method::DELETE, ["playbacks", playback_id]) => {
+                match state.playbacks.get_mut(*playback_id) {
+                    Some(sim_playback) if sim_playback.playback.state == PlaybackState::Playing => {
+                        sim_playback.playback.state = PlaybackState::Done;
+                        self.emit(AriEvent::PlaybackFinished(PlaybackFinished {
+                            envelope: envelope(&sim_playback.app),
+                            playback: sim_playback.playback.clone(),
+                        }));
+                        no_content()
+                    }
+                    _ => error_response(StatusCode::NOT_FOUND, "Playback not found"),
+                }
+            }
+            (&Method::POST, ["recordings", "live", name, "stop"]) => {
+                match state.recordings.get_mut(*name) {
+                    Some(sim_recording)
+                        if sim_recording.recording.state == RecordingState::Recording =>
+                    {
+                        sim_recording.recording.state = RecordingState::Done;
+                        self.emit(AriEvent::RecordingFinished(RecordingFinished {
+                            envelope: envelope(&sim_recording.app),
+                            recording: sim_recording.recording.clone(),
+                        }));
+                        no_content()
+                    }
+                    _ => error_response(StatusCode::NOT_FOUND, "Recording not found"),
+                }
+            }
+            _ => return None,
+        };
+        Some(response)
+    }
+
+    fn originate(&self, state: &mut SimulationState, body: &Value) -> MockResponse {
+        let endpoint = match body["endpoint"].as_str() {
+            Some(endpoint) => endpoint,
+            None => return error_response(StatusCode::BAD_REQUEST, "Endpoint must be specified"),
+        };
+        let app = body["app"].as_str();
+        if app.is_none() && body["extension"].as_str().is_none() {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "Application or extension must be specified",
+            );
+        }
+        // originated channel is answered immediately
+        let channel = state.new_channel_with_id(
+            body["channelId"].as_str(),
+            endpoint,
+            endpoint.rsplit('/').next().unwrap_or_default(),
+            ChannelState::Up,
+            app,
+            string_map(&body["variables"]),
+        );
+        if app.is_some() {
+            self.emit_stasis_start(state, &channel.id, app_args(body));
+        }
+        json_response(StatusCode::OK, &channel)
+    }
+
+    fn create_channel(&self, state: &mut SimulationState, body: &Value) -> MockResponse {
+        let (endpoint, app) = match (body["endpoint"].as_str(), body["app"].as_str()) {
+            (Some(endpoint), Some(app)) => (endpoint, app),
+            _ => {
+                return error_response(
+                    StatusCode::BAD_REQUEST,
+                    "Endpoint and application must be specified",
+                )
+            }
+        };
+        let channel = state.new_channel_with_id(
+            body["channelId"].as_str(),
+            endpoint,
+            endpoint.rsplit('/').next().unwrap_or_default(),
+            ChannelState::Down,
+            Some(app),
+            string_map(&body["variables"]),
+        );
+        self.emit_stasis_start(state, &channel.id, app_args(body));
+        json_response(StatusCode::OK, &channel)
+    }
+
+    fn answer(&self, state: &mut SimulationState, channel_id: &str) -> MockResponse {
+        let sim_channel = state.channels.get_mut(channel_id).unwrap();
+        if sim_channel.channel.state != ChannelState::Up {
+            sim_channel.channel.state = ChannelState::Up;
+            self.emit(AriEvent::ChannelStateChange(ChannelStateChange {
+                envelope: envelope(&sim_channel.app),
+                channel: sim_channel.channel.clone(),
+            }));
+        }
+        no_content()
+    }
+
+    fn play(
+        self: &Arc<Self>,
+        state: &mut SimulationState,
+        channel_id: &str,
+        body: &Value,
+    ) -> MockResponse {
+        let media = match body["media"].as_str() {
+            Some(media) => media.to_owned(),
+            None => return error_response(StatusCode::BAD_REQUEST, "Media must be specified"),
+        };
+        let playback_id = match body["playbackId"].as_str() {
+            Some(playback_id) => playback_id.to_owned(),
+            None => format!("playback-{}", state.next_id()),
+        };
+        let duration = state
+            .media_durations
+            .get(&media)
+            .copied()
+            .unwrap_or(state.default_playback_duration);
+
+        let playback = Playback {
+            id: playback_id.clone(),
+            media_uri: media,
+            next_media_uri: None,
+            target_uri: format!("channel:{}", channel_id),
+            language: body["lang"].as_str().unwrap_or("en").into(),
+            state: PlaybackState::Playing,
+        };
+        let app = state.channels[channel_id].app.clone();
+        self.emit(AriEvent::PlaybackStarted(PlaybackStarted {
+            envelope: envelope(&app),
+            playback: playback.clone(),
+        }));
+        state.playback_order.push(playback_id.clone());
+        state.playbacks.insert(
+            playback_id.clone(),
+            SimPlayback {
+                playback: playback.clone(),
+                app,
+            },
+        );
+
+        let simulation = self.clone();
+        tokio::spawn(async move {
+            sleep(duration).await;
+            let mut state = simulation.state.lock().unwrap();
+            if let Some(sim_playback) = state.playbacks.get_mut(&playback_id) {
+                // might have been stopped in the meantime
+                if sim_playback.playback.state == PlaybackState::Playing {
+                    sim_playback.playback.state = PlaybackState::Done;
+                    simulation.emit(AriEvent::PlaybackFinished(PlaybackFinished {
+                        envelope: envelope(&sim_playback.app),
+                        playback: sim_playback.playback.clone(),
+                    }));
+                }
+            }
+        });
+
+        json_response(StatusCode::CREATED, &playback)
+    }
+
+    fn continue_in_dialplan(&self, state: &mut SimulationState, channel_id: &str) -> MockResponse {
+        let sim_channel = state.channels.get_mut(channel_id).unwrap();
+        let app = std::mem::take(&mut sim_channel.app);
+        self.emit(AriEvent::StasisEnd(StasisEnd {
+            envelope: envelope(&app),
+            channel: sim_channel.channel.clone(),
+        }));
+        no_content()
+    }
+
+    fn set_variable(
+        &self,
+        state: &mut SimulationState,
+        channel_id: &str,
+        name: String,
+        value: String,
+    ) -> MockResponse {
+        let sim_channel = state.channels.get_mut(channel_id).unwrap();
+        sim_channel.variables.insert(name.clone(), value.clone());
+        self.emit(AriEvent::ChannelVarset(ChannelVarset {
+            envelope: envelope(&sim_channel.app),
+            channel: sim_channel.channel.clone(),
+            variable: name,
+            value,
+        }));
+        no_content()
+    }
+
+    fn record(&self, state: &mut SimulationState, channel_id: &str, body: &Value) -> MockResponse {
+        let name = body["name"].as_str().unwrap_or(channel_id).to_owned();
+        if state.recordings.contains_key(&name) && body["ifExists"].as_str() != Some("overwrite") {
+            return error_response(
+                StatusCode::CONFLICT,
+                "Recording with the same name already exists",
+            );
+        }
+        let recording = Recording {
+            name: name.clone(),
+            format: body["format"].as_str().unwrap_or("wav").into(),
+            state: RecordingState::Recording,
+            target_uri: format!("channel:{}", channel_id),
+        };
+        let app = state.channels[channel_id].app.clone();
+        self.emit(AriEvent::RecordingStarted(RecordingStarted {
+            envelope: envelope(&app),
+            recording: recording.clone(),
+        }));
+        state.recordings.insert(
+            name,
+            SimRecording {
+                recording: recording.clone(),
+                app,
+            },
+        );
+        json_response(StatusCode::CREATED, &recording)
+    }
+}
+
+fn envelope(app: &str) -> EventEnvelope {
+    EventEnvelope {
+        asterisk_id: Some("00:00:00:00:00:00".into()),
+        application: app.to_owned(),
+        timestamp: now(),
+    }
+}
+
+#[cfg(feature = "parse-event-datetimes")]
+fn now() -> Timestamp {
+    chrono::Local::now().fixed_offset()
+}
+
+#[cfg(not(feature = "parse-event-datetimes"))]
+fn now() -> Timestamp {
+    chrono::Local::now()
+        .format("%Y-%m-%dT%H:%M:%S%.3f%z")
+        .to_string()
+}
+
+fn app_args(body: &Value) -> Vec<String> {
+    body["appArgs"]
+        .as_str()
+        .filter(|app_args| !app_args.is_empty())
+        .map(|app_args| app_args.split(',').map(str::to_owned).collect())
+        .unwrap_or_default()
+}
+
+fn string_map(value: &Value) -> HashMap<String, String> {
+    value
+        .as_object()
+        .map(|object| {
+            object
+                .iter()
+                .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_owned())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn json_response<T: Serialize>(status: StatusCode, body: &T) -> MockResponse {
+    MockResponse {
+        status,
+        body: serde_json::to_string(body).expect("simulator: unable to serialize response"),
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> MockResponse {
+    json_response(status, &serde_json::json!({ "message": message }))
+}
+
+fn no_content() -> MockResponse {
+    MockResponse {
+        status: StatusCode::NO_CONTENT,
+        body: String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::channels::ChannelsAPI;
+    use crate::dtmf::{DtmfOptions, DtmfResult};
+    use crate::errors::ApiErrorKind;
+    use crate::models::channels::OriginateOptions;
+    use tokio::sync::broadcast::Receiver;
+    use tokio::time::Instant;
+
+    async fn next_event(events: &mut Receiver<AriEvent>) -> AriEvent {
+        events.recv().await.unwrap()
+    }
+
+    // cargo test -- --show-output test_simulated_call_flow
+    #[tokio::test(start_paused = true)]
+    async fn test_simulated_call_flow() {
+        let simulator = AsteriskSimulator::start().await;
+        simulator.set_media_duration("sound:enter-pin", Duration::from_secs(3));
+
+        let client = simulator.client();
+        let mut events = client.subscribe_events();
+        let loop_client = client.clone();
+        tokio::spawn(async move { loop_client.ari_processing_loop(vec!["ivr".into()]).await });
+        simulator.wait_for_websocket().await;
+
+        let channel = simulator.incoming_call("ivr", "1004", &["en-US"]);
+        match next_event(&mut events).await {
+            AriEvent::StasisStart(event) => {
+                assert_eq!(event.channel.id, channel.id);
+                assert_eq!(event.args, vec!["en-US".to_string()]);
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        client.answer(&channel.id).await.unwrap();
+        assert!(matches!(
+            next_event(&mut events).await,
+            AriEvent::ChannelStateChange(event) if event.channel.state == ChannelState::Up
+        ));
+
+        // digits are entered once the prompt is finished
+        let started = Instant::now();
+        let dtmf_client = client.clone();
+        let channel_id = channel.id.clone();
+        let collect = tokio::spawn(async move {
+            dtmf_client
+                .collect_dtmf(
+                    &channel_id,
+                    DtmfOptions {
+                        prompt: Some("sound:enter-pin".into()),
+                        barge_in: false,
+                        ..Default::default()
+                    },
+                )
+                .await
+        });
+        loop {
+            if let AriEvent::PlaybackFinished(_) = next_event(&mut events).await {
+                break;
+            }
+        }
+        assert!(started.elapsed() >= Duration::from_secs(3));
+        simulator.send_dtmf(&channel.id, "12#");
+        assert_eq!(
+            collect.await.unwrap().unwrap(),
+            DtmfResult::Terminated {
+                digits: "12".into(),
+                terminator: '#'
+            }
+        );
+        assert_eq!(simulator.playbacks()[0].media_uri, "sound:enter-pin");
+
+        client.set_variable(&channel.id, "PIN", "12").await.unwrap();
+        assert_eq!(
+            simulator.variable(&channel.id, "PIN").as_deref(),
+            Some("12")
+        );
+
+        client.hangup(&channel.id).await.unwrap();
+        assert!(simulator.channel(&channel.id).is_none());
+        let err = client.answer(&channel.id).await.unwrap_err();
+        assert_eq!(err.api_error_kind(), Some(ApiErrorKind::NotFound));
+
+        client.shutdown_handle().shutdown();
+    }
+
+    // cargo test -- --show-output test_simulated_originate
+    #[tokio::test(start_paused = true)]
+    async fn test_simulated_originate() {
+        let simulator = AsteriskSimulator::start().await;
+        let client = simulator.client();
+        let mut events = client.subscribe_events();
+        let loop_client = client.clone();
+        tokio::spawn(async move { loop_client.ari_processing_loop(vec!["dialer".into()]).await });
+        simulator.wait_for_websocket().await;
+
+        let channel = client
+            .originate(
+                "PJSIP/1001",
+                OriginateOptions::default()
+                    .app("dialer")
+                    .channel_id("outbound-1")
+                    .variable("CAMPAIGN", "spring"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(channel.id, "outbound-1");
+        assert!(matches!(
+            next_event(&mut events).await,
+            AriEvent::StasisStart(event) if event.channel.id == "outbound-1"
+        ));
+        assert_eq!(
+            client.get_variable("outbound-1", "CAMPAIGN").await.unwrap(),
+            "spring"
+        );
+        assert_eq!(client.list_channels().await.unwrap().len(), 1);
+
+        // playback interrupted by caller hangup
+        client
+            .play("outbound-1", "sound:hello-world", None, None, None, None)
+            .await
+            .unwrap();
+        simulator.caller_hangup("outbound-1");
+        let mut types = vec![];
+        while types.last() != Some(&"ChannelDestroyed") {
+            types.push(next_event(&mut events).await.event_type());
+        }
+        assert_eq!(
+            types,
+            vec![
+                "PlaybackStarted",
+                "PlaybackFinished",
+                "ChannelHangupRequest",
+                "StasisEnd",
+                "ChannelDestroyed"
+            ]
+        );
+
+        client.shutdown_handle().shutdown();
+    }
+}